@@ -0,0 +1,164 @@
+//benchmarks the literal fast path of the Searcher against the ways of
+//searching it replaces
+//
+//  cargo run --release --example bench          (32 MB per corpus)
+//  cargo run --release --example bench -- 128   (128 MB per corpus)
+//
+//the corpora are generated, so there's nothing to download and every run
+//searches the same text. for every corpus and needle it times:
+//  lines+contains   contents.lines() and line.contains(), the original minigrep
+//  line by line     minigrep::Searcher reading every line and matching it,
+//                   which is what it does for a pattern that isn't plain text
+//  searcher         minigrep::Searcher as the command line uses it, which
+//                   skips to the lines with the needle in them
+//and checks that all three found the same lines
+
+use std::io::BufReader;
+use std::time::{Duration, Instant};
+
+use minigrep::{escape, Match, Matcher, Regex, Searcher};
+
+//a tiny xorshift rng, the benchmark shouldn't need a crate for randomness
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+const WORDS: &[&str] = &[
+    "the", "of", "and", "to", "in", "a", "is", "that", "for", "it", "as", "was", "with", "be", "by", "on", "not",
+    "he", "this", "are", "or", "his", "from", "at", "which", "but", "have", "an", "had", "they", "you", "were",
+    "their", "one", "all", "we", "can", "her", "has", "there", "been", "if", "more", "when", "will", "would",
+    "who", "so", "no", "rust", "borrow", "checker", "lifetime", "trait", "ownership", "compiler", "memory",
+];
+
+//english-ish text, a rare word now and then
+fn prose(size: usize, rng: &mut Rng) -> String {
+    let mut text = String::with_capacity(size + 200);
+    while text.len() < size {
+        let words = 8 + rng.below(10);
+        for i in 0..words {
+            if i > 0 {
+                text.push(' ');
+            }
+            text.push_str(if rng.below(5000) == 0 { "Sherlock" } else { WORDS[rng.below(WORDS.len())] });
+        }
+        text.push_str(".\n");
+    }
+    text
+}
+
+//web server style log lines, with a rare client ip
+fn logs(size: usize, rng: &mut Rng) -> String {
+    let mut text = String::with_capacity(size + 200);
+    let paths = ["/", "/index.html", "/api/v1/users", "/static/app.js", "/login"];
+    while text.len() < size {
+        let ip = if rng.below(2000) == 0 {
+            "10.6.6.6".to_string()
+        } else {
+            format!("192.168.{}.{}", rng.below(256), rng.below(256))
+        };
+        text.push_str(&format!(
+            "{ip} - - [17/Oct/2026:{:02}:{:02}:{:02} +0000] \"GET {} HTTP/1.1\" {} {}\n",
+            rng.below(24),
+            rng.below(60),
+            rng.below(60),
+            paths[rng.below(paths.len())],
+            [200, 200, 200, 304, 404][rng.below(5)],
+            rng.below(50_000)
+        ));
+    }
+    text
+}
+
+//minified-javascript-like lines, a few kilobytes each
+fn long_lines(size: usize, rng: &mut Rng) -> String {
+    let mut text = String::with_capacity(size + 10_000);
+    while text.len() < size {
+        for _ in 0..500 + rng.below(500) {
+            text.push_str(WORDS[rng.below(WORDS.len())]);
+            text.push(if rng.below(3) == 0 { ';' } else { '.' });
+        }
+        text.push('\n');
+    }
+    text
+}
+
+//the regex without its literal, so the Searcher can't skip any lines
+struct LineByLine<'r>(&'r Regex);
+
+impl Matcher for LineByLine<'_> {
+    fn find_at(&self, text: &str, start: usize) -> Option<(usize, usize)> {
+        self.0.find_at(text, start)
+    }
+}
+
+//how many lines the Searcher selects, reading through the same size of
+//buffer the command line reads files with
+fn count<M: Matcher>(matcher: &M, contents: &str) -> usize {
+    let mut count = 0;
+    let mut sink = |_: &Match| {
+        count += 1;
+        Ok(true)
+    };
+    let reader = BufReader::with_capacity(64 * 1024, contents.as_bytes());
+    Searcher::new().search_reader(matcher, reader, &mut sink).unwrap();
+    count
+}
+
+//runs f a few times and keeps the fastest, to keep noise out
+fn time<T>(mut f: impl FnMut() -> T) -> (Duration, T) {
+    let mut best = Duration::MAX;
+    let mut result = None;
+    for _ in 0..3 {
+        let start = Instant::now();
+        let r = f();
+        best = best.min(start.elapsed());
+        result = Some(r);
+    }
+    (best, result.unwrap())
+}
+
+fn main() {
+    let mb: usize = std::env::args().nth(1).and_then(|arg| arg.parse().ok()).unwrap_or(32);
+    let size = mb * 1024 * 1024;
+    let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+
+    let corpora = [
+        ("prose", prose(size, &mut rng), vec!["Sherlock", "the", "ownership", "zqxjk"]),
+        ("logs", logs(size, &mut rng), vec!["10.6.6.6", "GET", "404 ", "zqxjk"]),
+        ("long lines", long_lines(size, &mut rng), vec!["checker;rust", "zqxjk"]),
+    ];
+
+    println!("{:<12}{:<14}{:>9}  {:>18}{:>18}{:>18}", "corpus", "needle", "lines", "lines+contains", "line by line", "searcher");
+    for (name, contents, needles) in &corpora {
+        for needle in needles {
+            let re = Regex::build(&escape(needle), false).unwrap();
+
+            let (contains, expected) = time(|| contents.lines().filter(|line| line.contains(needle)).count());
+            let (lines, by_line) = time(|| count(&LineByLine(&re), contents));
+            let (searcher, by_searcher) = time(|| count(&re, contents));
+            assert_eq!((expected, expected), (by_line, by_searcher), "{name}: {needle}");
+
+            let speed = |d: Duration| format!("{:>8.1?} {:>5.0}MB/s", d, mb as f64 / d.as_secs_f64());
+            println!(
+                "{:<12}{:<14}{:>9}  {:>18}{:>18}{:>18}",
+                name,
+                format!("{needle:?}"),
+                expected,
+                speed(contains),
+                speed(lines),
+                speed(searcher)
+            );
+        }
+    }
+}
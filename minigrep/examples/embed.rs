@@ -0,0 +1,58 @@
+//minigrep as a library: counts the lines mentioning a word in every file
+//given, case insensitively, and prints the first one of each file
+//
+//  cargo run --example embed -- nobody poem.txt src/lib.rs
+//
+//the command line is one way of using the Searcher, this is another: the
+//results go into our own Sink instead of being printed the grep way
+
+use std::env;
+use std::io;
+use std::path::Path;
+use std::process;
+
+use minigrep::{Literal, Match, Searcher, Sink, Stats};
+
+#[derive(Default)]
+struct FirstLine {
+    first: Option<(usize, String)>,
+    lines: usize,
+}
+
+impl Sink for FirstLine {
+    fn matched(&mut self, m: &Match) -> io::Result<bool> {
+        if self.first.is_none() {
+            self.first = Some((m.line_number, m.line.to_string()));
+        }
+        Ok(true)
+    }
+
+    fn finish(&mut self, stats: &Stats) -> io::Result<()> {
+        self.lines = stats.matched_lines;
+        Ok(())
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (word, paths) = match args.split_first() {
+        Some((word, paths)) if !paths.is_empty() => (word, paths),
+        _ => {
+            eprintln!("usage: embed WORD FILE...");
+            process::exit(2);
+        }
+    };
+
+    let matcher = Literal::ignore_case(word);
+    let searcher = Searcher::new();
+    for path in paths {
+        let mut sink = FirstLine::default();
+        match searcher.search_path(&matcher, Path::new(path), &mut sink) {
+            Ok(_) => match sink.first {
+                Some((line_number, line)) => println!("{path}: {} lines, first on line {line_number}: {line}", sink.lines),
+                None => println!("{path}: no lines"),
+            },
+            Err(err) => eprintln!("{path}: {err}"),
+        }
+    }
+}
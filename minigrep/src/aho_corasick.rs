@@ -0,0 +1,172 @@
+use crate::casefold;
+
+//an aho-corasick automaton: a trie of all the patterns plus a "fail" link
+//on every node pointing at the longest suffix of that node's text that is
+//also a prefix in the trie. reading the text one char at a time and
+//following fail links when there's no way forward finds every occurrence
+//of every pattern in a single pass, so grepping for 10,000 strings costs
+//about as much per char as grepping for one
+//
+//it's used by Regex when every pattern is plain text (-F, or patterns
+//without any special chars, which is what a list of IOC strings from -f
+//looks like), see Regex::build_many
+
+#[derive(Debug, Clone, Default)]
+struct State {
+    next: Vec<(char, usize)>, //sorted by char so lookups can binary search
+    fail: usize,
+    matches: Vec<usize>, //patterns ending here, including the ones of the fail chain
+}
+
+#[derive(Debug, Clone)]
+pub struct AhoCorasick {
+    states: Vec<State>, //states[0] is the root
+    lens: Vec<usize>,   //the length of every pattern in chars
+    max_len: usize,
+    ignore_case: bool,
+}
+
+impl AhoCorasick {
+    /// Builds the automaton. None of the patterns may be empty.
+    pub fn new(patterns: &[Vec<char>], ignore_case: bool) -> AhoCorasick {
+        let mut ac = AhoCorasick {
+            states: vec![State::default()],
+            lens: patterns.iter().map(|p| p.len()).collect(),
+            max_len: patterns.iter().map(|p| p.len()).max().unwrap_or(0),
+            ignore_case,
+        };
+
+        for (id, pattern) in patterns.iter().enumerate() {
+            let mut state = 0;
+            for &c in pattern {
+                let c = if ignore_case { casefold::fold(c) } else { c };
+                state = match ac.goto(state, c) {
+                    Some(next) => next,
+                    None => ac.add_state(state, c),
+                };
+            }
+            ac.states[state].matches.push(id);
+        }
+
+        //fail links, breadth first so a state's fail target (which is
+        //always shallower) is finished before the state itself
+        let mut queue = std::collections::VecDeque::new();
+        queue.extend(ac.states[0].next.iter().map(|&(_, child)| child));
+        while let Some(state) = queue.pop_front() {
+            for i in 0..ac.states[state].next.len() {
+                let (c, child) = ac.states[state].next[i];
+                let mut fail = ac.states[state].fail;
+                let target = loop {
+                    if let Some(next) = ac.goto(fail, c) {
+                        break next;
+                    }
+                    if fail == 0 {
+                        break 0;
+                    }
+                    fail = ac.states[fail].fail;
+                };
+                ac.states[child].fail = target;
+                let inherited = ac.states[target].matches.clone();
+                ac.states[child].matches.extend(inherited);
+                queue.push_back(child);
+            }
+        }
+        ac
+    }
+
+    fn goto(&self, state: usize, c: char) -> Option<usize> {
+        let next = &self.states[state].next;
+        next.binary_search_by_key(&c, |&(c, _)| c).ok().map(|i| next[i].1)
+    }
+
+    fn add_state(&mut self, from: usize, c: char) -> usize {
+        let id = self.states.len();
+        self.states.push(State::default());
+        let next = &mut self.states[from].next;
+        let at = next.partition_point(|&(x, _)| x < c);
+        next.insert(at, (c, id));
+        id
+    }
+
+    /// Finds the leftmost occurrence at or after `start` that `accept` agrees
+    /// to (it gets the byte range and the pattern index, e.g. to check word
+    /// boundaries). When several start at the same place the lowest pattern
+    /// index wins, like the first branch of an alternation. Returns
+    /// (start, end, pattern index).
+    pub fn find_at(&self, text: &str, start: usize, mut accept: impl FnMut(usize, usize, usize) -> bool) -> Option<(usize, usize, usize)> {
+        let mut state = 0;
+        let mut best: Option<(usize, usize, usize, usize)> = None; //(start, end, pattern, start in chars)
+
+        for (i, (offset, c)) in text[start..].char_indices().enumerate() {
+            //nothing that ends later than this can start at or before the best one
+            if let Some((_, _, _, best_start)) = best {
+                if i + 1 > best_start + self.max_len {
+                    break;
+                }
+            }
+
+            let end = start + offset + c.len_utf8();
+            let c = if self.ignore_case { casefold::fold(c) } else { c };
+            state = loop {
+                if let Some(next) = self.goto(state, c) {
+                    break next;
+                }
+                if state == 0 {
+                    break 0;
+                }
+                state = self.states[state].fail;
+            };
+
+            for &id in &self.states[state].matches {
+                let chars = i + 1 - self.lens[id];
+                if best.is_some_and(|(_, _, best_id, best_start)| (best_start, best_id) < (chars, id)) {
+                    continue;
+                }
+                //with ignore_case the text may use more bytes than the pattern (K vs k), so count back in chars
+                let begin = match text[..end].char_indices().rev().nth(self.lens[id] - 1) {
+                    Some((begin, _)) => begin,
+                    None => continue,
+                };
+                if accept(begin, end, id) {
+                    best = Some((begin, end, id, chars));
+                }
+            }
+        }
+
+        best.map(|(begin, end, id, _)| (begin, end, id))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn build(patterns: &[&str], ignore_case: bool) -> AhoCorasick {
+        let patterns: Vec<Vec<char>> = patterns.iter().map(|p| p.chars().collect()).collect();
+        AhoCorasick::new(&patterns, ignore_case)
+    }
+
+    #[test]
+    fn finds_leftmost_first() {
+        let ac = build(&["he", "she", "his", "hers"], false);
+        assert_eq!(Some((1, 4, 1)), ac.find_at("ushers", 0, |_, _, _| true));
+        assert_eq!(Some((2, 4, 0)), ac.find_at("ushers", 2, |_, _, _| true));
+        assert_eq!(None, ac.find_at("hx", 0, |_, _, _| true));
+
+        //same start: the earlier pattern wins even though it's shorter
+        let ac = build(&["ab", "abcd"], false);
+        assert_eq!(Some((0, 2, 0)), ac.find_at("abcd", 0, |_, _, _| true));
+        let ac = build(&["bcd", "abcde"], false);
+        assert_eq!(Some((0, 5, 1)), ac.find_at("abcde", 0, |_, _, _| true));
+    }
+
+    #[test]
+    fn rejected_hits_and_case() {
+        let ac = build(&["cat"], false);
+        assert_eq!(Some((13, 16, 0)), ac.find_at("concatenate, cat", 0, |start, _, _| start != 3));
+
+        let ac = build(&["kelvin", "σ"], true);
+        assert_eq!(Some((0, 8, 0)), ac.find_at("\u{212A}ELVIN", 0, |_, _, _| true));
+        assert_eq!(Some((1, 3, 1)), ac.find_at("xς", 0, |_, _, _| true));
+    }
+}
@@ -0,0 +1,142 @@
+//unicode simple case folding, one char in and one char out, so matching
+//case insensitively never has to allocate or change the length of the
+//text (which lowercasing whole lines did: "İ".to_lowercase() is two chars)
+//
+//two chars are equal ignoring case when they fold to the same char.
+//lowercasing alone isn't enough for that, e.g. the greek final sigma ς
+//lowercases to itself but has to match σ and Σ. going through uppercase
+//first puts ς, σ and Σ in one group, and the same for ſ and s, ϐ and β,
+//the kelvin sign and k, and so on.
+//
+//chars with no one-char fold (like ß, whose uppercase is "SS", or the
+//turkish İ whose lowercase is "i̇") only match themselves and their
+//one-char relatives (ẞ for ß), which is what simple folding says too
+
+/// Folds a char so that `fold(a) == fold(b)` exactly when a and b are the same letter ignoring case.
+pub fn fold(c: char) -> char {
+    //ascii is by far the most common case, so don't bother unicode with it
+    if c.is_ascii() {
+        return c.to_ascii_lowercase();
+    }
+    //the turkish dotless ı uppercases to the plain I, but folding keeps it separate
+    if c == 'ı' {
+        return c;
+    }
+
+    let upper = match single(c.to_uppercase()) {
+        Some(upper) => upper,
+        None => c,
+    };
+    match single(upper.to_lowercase()) {
+        Some(lower) => lower,
+        //ß and friends: the uppercase was more than one char, so fold from the lowercase side
+        None => single(c.to_lowercase()).unwrap_or(c),
+    }
+}
+
+//the char an iterator like to_lowercase() yields, if it yields exactly one
+fn single(mut chars: impl Iterator<Item = char>) -> Option<char> {
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    }
+}
+
+//folds with more members than a lowercase and an uppercase letter. they're
+//needed for character classes, where "does [a-z] contain some version of
+//this char" can't be answered by folding the class bounds
+const EXTRA_VARIANTS: &[(char, char)] = &[
+    ('k', '\u{212A}'), //kelvin sign
+    ('s', 'ſ'),
+    ('å', '\u{212B}'), //angstrom sign
+    ('ω', '\u{2126}'), //ohm sign
+    ('μ', 'µ'),        //micro sign
+    ('σ', 'ς'),
+    ('β', 'ϐ'),
+    ('ε', 'ϵ'),
+    ('θ', 'ϑ'),
+    ('θ', 'ϴ'),
+    ('ι', '\u{0345}'),
+    ('ι', '\u{1FBE}'),
+    ('κ', 'ϰ'),
+    ('π', 'ϖ'),
+    ('ρ', 'ϱ'),
+    ('φ', 'ϕ'),
+    ('ṡ', 'ẛ'),
+    ('ß', 'ẞ'),
+    ('ǆ', 'ǅ'),
+    ('ǉ', 'ǈ'),
+    ('ǌ', 'ǋ'),
+    ('ǳ', 'ǲ'),
+    ('в', 'ᲀ'),
+    ('д', 'ᲁ'),
+    ('о', 'ᲂ'),
+    ('с', 'ᲃ'),
+    ('т', 'ᲄ'),
+    ('т', 'ᲅ'),
+    ('ъ', 'ᲆ'),
+    ('ѣ', 'ᲇ'),
+    ('ꙋ', 'ᲈ'),
+];
+
+/// Every char that folds the same as `c`, including `c` itself (possibly more than once).
+pub fn variants(c: char) -> impl Iterator<Item = char> {
+    let folded = fold(c);
+    let upper = single(folded.to_uppercase()).filter(|&u| fold(u) == folded);
+    let extra = EXTRA_VARIANTS
+        .iter()
+        .filter(move |(base, _)| *base == folded)
+        .map(|&(_, variant)| variant);
+
+    [c, folded].into_iter().chain(upper).chain(extra)
+}
+
+/// True if `pattern` has an uppercase letter outside of an escape like \W or \S.
+/// Smart case searches case insensitively only when this is false.
+pub fn has_uppercase(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            chars.next(); //\W, \S, \D and \B are escapes, not letters
+        } else if c.is_uppercase() {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn folds_like_unicode() {
+        assert_eq!(fold('σ'), fold('Σ'));
+        assert_eq!(fold('ς'), fold('Σ'));
+        assert_eq!(fold('ſ'), fold('S'));
+        assert_eq!(fold('\u{212A}'), fold('k'));
+        assert_eq!(fold('ẞ'), fold('ß'));
+        assert_ne!(fold('ß'), fold('s'));
+        assert_ne!(fold('ı'), fold('i'));
+        assert_ne!(fold('İ'), fold('i'));
+        assert_eq!('İ', fold('İ'));
+    }
+
+    #[test]
+    fn every_variant_folds_the_same() {
+        for c in ['a', 'K', 'σ', 'ς', 'ß', 'ǅ', 'т', 'İ'] {
+            for v in variants(c) {
+                assert_eq!(fold(c), fold(v), "{c} vs {v}");
+            }
+        }
+        assert!(variants('ς').any(|v| v == 'Σ'));
+        assert!(variants('k').any(|v| v == '\u{212A}'));
+    }
+
+    #[test]
+    fn smart_case_letters() {
+        assert!(!has_uppercase(r"foo\Wbar\S"));
+        assert!(has_uppercase("Foo"));
+        assert!(has_uppercase("straße|Ärger"));
+    }
+}
@@ -0,0 +1,1032 @@
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use crate::casefold;
+use crate::encoding::Encoding;
+use crate::records::Field;
+use crate::searcher::BinaryFiles;
+
+pub const USAGE: &str = "\
+usage: minigrep [OPTIONS] PATTERN [PATH...]
+       minigrep [OPTIONS] -e PATTERN... [PATH...]
+       minigrep index build DIR
+
+with no PATH, or a PATH of -, standard input is searched.
+
+options:
+  -e, --regexp PATTERN       search for PATTERN (can be given more than once)
+  -f, --file FILE            search for every pattern in FILE, one per line (- for stdin)
+  -i, --ignore-case          case insensitive search (also IGNORE_CASE=1)
+      --case-sensitive       case sensitive search, even if IGNORE_CASE is set
+  -S, --smart-case           ignore case unless the pattern has an uppercase letter
+  -w, --word-regexp          only match whole words
+  -x, --line-regexp          only match whole lines
+  -U, --multiline            let matches go across lines (with \\n, \\s or (?s).), printing
+                             every line of a match. ^ and $ match at every line, and
+                             each file is read into memory first
+  -F, --fixed-strings        treat the patterns as plain text, not regexes
+      --fuzzy K              match text within K edits (a char inserted, deleted or
+                             changed) of a pattern, which is plain text. the closest
+                             match is highlighted, and its edits printed before the line
+      --csv, --tsv           read the files as comma or tab separated records with a
+                             header, matching only the column --field NAME and
+                             printing the records that match whole
+      --jsonl                read the files as json lines, matching only the value at
+                             --path PATH (like .user.name or .items[0].id, or . for
+                             the whole line) and printing the lines that match
+  -v, --invert-match         select the lines that don't match
+  -c, --count                print the number of selected lines per file
+  -l, --files-with-matches   print only the names of files with a selected line
+  -L, --files-without-match  print only the names of files with no selected line
+  -q, --quiet                print nothing, exit with 0 if anything was selected
+  -s, --no-messages          don't print errors about unreadable files
+  -n, --line-number          print line numbers
+  -b, --byte-offset          print the byte offset of each line
+  -A, --after-context NUM    print NUM lines after each match
+  -B, --before-context NUM   print NUM lines before each match
+  -C, --context NUM          print NUM lines before and after each match
+      --color[=WHEN]         highlight matches: auto (the default), always or never
+      --json                 print results as json lines (like rg --json)
+      --show-pattern         print which pattern matched each line
+      --replace REPL         print selected lines with every match replaced by REPL,
+                             where $1 or ${1} is group 1, $0 the whole match, $$ a $
+      --in-place[=SUFFIX]    with --replace, rewrite the files instead of printing,
+                             keeping the old ones with SUFFIX added (.bak if not
+                             given, --in-place= for no backup)
+  -a, --text                 search binary files as if they were text
+      --binary-files TYPE    what to do with binary files: binary (the default, print
+                             \"Binary file X matches\" instead of the lines),
+                             text (same as -a) or without-match (skip them)
+  -z, --search-zip           search the contents of gzip compressed files
+  -E, --encoding ENC         what files without a byte order mark are in: utf-8 (the
+                             default), utf-16le, utf-16be, latin1 or windows-1252.
+                             a file with one is in utf-8 or utf-16 going by it
+      --follow               keep searching the files as they grow, like tail -f, and
+                             from the start again when one is truncated or rotated
+  -j, --threads NUM          search up to NUM files at once, at most 1024 (default: one
+                             per cpu)
+      --index                only search the files that the index (see below) says
+                             could match, plus the ones changed since it was built
+      --include GLOB         only search files matching GLOB
+      --exclude GLOB         skip files and directories matching GLOB. for both, a GLOB
+                             without a / is matched against the name alone (so
+                             --exclude target skips every directory called target),
+                             one with a / against the path below the directory
+                             being searched (src/*.rs is DIR/src/*.rs)
+      --no-config            don't read the config file
+  -h, --help                 print this help
+  -V, --version              print the version
+      --                     treat everything after this as a pattern or path
+
+minigrep index build DIR makes a trigram index of the files under DIR for
+--index, and brings it up to date when run again, reading only the files
+that changed. to search for \"index\" in a file called build, use -e index.
+
+defaults for any option can go in a config file, one per line with # for
+comments, read from $MINIGREP_CONFIG or else ~/.config/minigrep/config.
+IGNORE_CASE in the environment beats the config file, and the command line
+beats both. NO_COLOR only turns off --color=auto.
+
+exit status is 0 if a line was selected, 1 if not and 2 if there was an error.";
+
+pub const VERSION: &str = match option_env!("CARGO_PKG_VERSION") {
+    Some(version) => version,
+    None => "dev",
+};
+
+//everything that can go wrong on the command line. --help and --version
+//are in here too bc they also mean "stop and don't search", main decides
+//that they go to stdout and aren't failures
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigError {
+    Help,
+    Version,
+    MissingPattern,
+    UnknownFlag(String),
+    MissingValue(String),
+    InvalidValue { flag: String, value: String },
+    Conflict(&'static str, &'static str), //two flags that can't be used together
+    PatternFile { path: String, msg: String }, //-f FILE couldn't be read
+    Requires(&'static str, &'static str), //the first flag only works with the second
+    ConfigFile { path: String, msg: String }, //the config file couldn't be read, or has a bad flag in it
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Help => write!(f, "{USAGE}"),
+            ConfigError::Version => write!(f, "minigrep {VERSION}"),
+            ConfigError::MissingPattern => write!(f, "no pattern given"),
+            ConfigError::UnknownFlag(flag) => write!(f, "unknown option '{flag}'"),
+            ConfigError::MissingValue(flag) => write!(f, "option '{flag}' needs a value"),
+            ConfigError::InvalidValue { flag, value } => write!(f, "invalid value '{value}' for option '{flag}'"),
+            ConfigError::Conflict(a, b) => write!(f, "options '{a}' and '{b}' can't be used together"),
+            ConfigError::PatternFile { path, msg } => write!(f, "couldn't read patterns from {path}: {msg}"),
+            ConfigError::Requires(a, b) => write!(f, "option '{a}' only works with '{b}'"),
+            ConfigError::ConfigFile { path, msg } => write!(f, "in config file {path}: {msg}"),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputMode {
+    Lines,            //the default, print every selected line
+    Count,            //-c
+    FilesWithMatches, //-l
+    FilesWithoutMatch, //-L
+    Quiet,            //-q, no output at all, only the exit status
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    Auto, //color if stdout is a terminal and NO_COLOR isn't set
+    Always,
+    Never,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub patterns: Vec<String>, //make this String and not &String so we don't have lifetime issues
+    pub paths: Vec<String>, //files and/or directories to search
+    pub ignore_case: bool,
+    pub word_regexp: bool,  //-w
+    pub line_regexp: bool,  //-x
+    pub fixed_strings: bool, //-F
+    pub invert_match: bool, //-v
+    pub mode: OutputMode,
+    pub no_messages: bool, //-s, don't complain about files that can't be read
+    pub include: Vec<String>, //--include GLOB, only search files matching one of these
+    pub exclude: Vec<String>, //--exclude GLOB, skip files and directories matching any of these
+    pub line_number: bool, //-n
+    pub byte_offset: bool, //-b
+    pub before_context: usize, //-B N (or -C N)
+    pub after_context: usize, //-A N (or -C N)
+    pub color: ColorChoice, //--color=WHEN
+    pub threads: Option<usize>, //-j N, None means one per cpu
+    pub json: bool, //--json
+    pub show_pattern: bool, //--show-pattern
+    pub replace: Option<String>, //--replace REPL
+    pub in_place: Option<String>, //--in-place, the backup suffix ("" for no backup)
+    pub search_zip: bool, //-z
+    pub binary_files: BinaryFiles, //-a and --binary-files
+    pub multiline: bool, //-U
+    pub follow: bool, //--follow
+    pub index: bool, //--index
+    pub fuzzy: Option<usize>, //--fuzzy K, how many edits a match can be from the pattern
+    pub field: Option<Field>, //--csv/--tsv --field NAME or --jsonl --path PATH
+    pub encoding: Encoding, //-E, what to decode files without a byte order mark as
+}
+
+//short flags are just another name for a long one, so everything below
+//only has to deal with long names
+fn long_name(short: char) -> Option<&'static str> {
+    let long = match short {
+        'e' => "regexp",
+        'f' => "file",
+        'i' => "ignore-case",
+        'w' => "word-regexp",
+        'x' => "line-regexp",
+        'F' => "fixed-strings",
+        'v' => "invert-match",
+        'c' => "count",
+        'l' => "files-with-matches",
+        'L' => "files-without-match",
+        'q' => "quiet",
+        's' => "no-messages",
+        'n' => "line-number",
+        'b' => "byte-offset",
+        'A' => "after-context",
+        'B' => "before-context",
+        'C' => "context",
+        'h' => "help",
+        'V' => "version",
+        'S' => "smart-case",
+        'j' => "threads",
+        'z' => "search-zip",
+        'a' => "text",
+        'U' => "multiline",
+        'E' => "encoding",
+        _ => return None,
+    };
+    Some(long)
+}
+
+//flags whose value can be left out, and what it is then
+fn default_value(long: &str) -> Option<&'static str> {
+    match long {
+        "color" | "colour" => Some("auto"), //--color is --color=auto
+        "in-place" => Some(".bak"),
+        _ => None,
+    }
+}
+
+fn takes_value(long: &str) -> bool {
+    matches!(
+        long,
+        "regexp"
+            | "file"
+            | "replace"
+            | "binary-files"
+            | "after-context"
+            | "before-context"
+            | "context"
+            | "include"
+            | "exclude"
+            | "threads"
+            | "fuzzy"
+            | "field"
+            | "path"
+            | "encoding"
+    )
+}
+
+//the environment variables that change the defaults. Config::build reads
+//them once and hands them on, so the parsing doesn't depend on the process
+//environment and the tests can say what's in it
+#[derive(Default)]
+struct Environment {
+    ignore_case: bool, //IGNORE_CASE is set, to anything
+}
+
+impl Environment {
+    fn read() -> Environment {
+        Environment {
+            ignore_case: env::var_os("IGNORE_CASE").is_some(),
+        }
+    }
+}
+
+//the parts of Config that only get their final value once every flag has been seen
+#[derive(Default)]
+struct Parsed {
+    patterns: Vec<String>,
+    pattern_file: bool, //an empty -f FILE still means the first positional arg is a path
+    positional: Vec<String>,
+    ignore_case: Option<bool>, //None means neither -i nor IGNORE_CASE said anything
+    smart_case: bool,
+    word_regexp: bool,
+    line_regexp: bool,
+    fixed_strings: bool,
+    invert_match: bool,
+    mode: Option<OutputMode>,
+    quiet: bool, //separate from mode bc -q wins no matter where it is
+    no_messages: bool,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    line_number: bool,
+    byte_offset: bool,
+    before_context: usize,
+    after_context: usize,
+    color: Option<ColorChoice>,
+    threads: Option<usize>,
+    json: bool,
+    show_pattern: bool,
+    replace: Option<String>,
+    in_place: Option<String>,
+    search_zip: bool,
+    binary_files: BinaryFiles,
+    multiline: bool,
+    follow: bool,
+    index: bool,
+    fuzzy: Option<usize>,
+    records: Option<&'static str>, //--csv, --tsv or --jsonl
+    field: Option<String>,
+    path: Option<String>,
+    encoding: Encoding,
+}
+
+impl Parsed {
+    //`flag` is what the user typed, for error messages
+    fn apply(&mut self, long: &str, flag: &str, value: Option<String>) -> Result<(), ConfigError> {
+        let value = value.unwrap_or_default(); //only empty for flags that don't take one
+        match long {
+            "regexp" => self.patterns.push(value),
+            "file" => {
+                self.patterns.extend(read_patterns(&value)?);
+                self.pattern_file = true;
+            }
+            //the last of -i, --case-sensitive and -S wins
+            "ignore-case" | "case-sensitive" => {
+                self.ignore_case = Some(long == "ignore-case");
+                self.smart_case = false;
+            }
+            "smart-case" => {
+                self.ignore_case = None;
+                self.smart_case = true;
+            }
+            "word-regexp" => self.word_regexp = true,
+            "line-regexp" => self.line_regexp = true,
+            "multiline" => self.multiline = true,
+            "follow" => self.follow = true,
+            "index" => self.index = true,
+            "fuzzy" => self.fuzzy = Some(number(flag, &value)?),
+            "csv" | "tsv" | "jsonl" => {
+                let records = match long {
+                    "csv" => "--csv",
+                    "tsv" => "--tsv",
+                    _ => "--jsonl",
+                };
+                match self.records {
+                    Some(other) if other != records => return Err(ConfigError::Conflict(other, records)),
+                    _ => self.records = Some(records),
+                }
+            }
+            "field" => self.field = Some(value),
+            "path" => self.path = Some(value),
+            "encoding" => match Encoding::from_name(&value) {
+                Some(encoding) => self.encoding = encoding,
+                None => {
+                    return Err(ConfigError::InvalidValue {
+                        flag: flag.to_string(),
+                        value,
+                    })
+                }
+            },
+            "fixed-strings" => self.fixed_strings = true,
+            "invert-match" => self.invert_match = true,
+            "count" => self.mode = Some(OutputMode::Count),
+            "files-with-matches" => self.mode = Some(OutputMode::FilesWithMatches),
+            "files-without-match" => self.mode = Some(OutputMode::FilesWithoutMatch),
+            "quiet" | "silent" => self.quiet = true,
+            "no-messages" => self.no_messages = true,
+            "line-number" => self.line_number = true,
+            "byte-offset" => self.byte_offset = true,
+            "after-context" => self.after_context = number(flag, &value)?,
+            "before-context" => self.before_context = number(flag, &value)?,
+            "context" => {
+                self.before_context = number(flag, &value)?;
+                self.after_context = self.before_context;
+            }
+            "color" | "colour" => {
+                self.color = Some(match value.as_str() {
+                    "auto" => ColorChoice::Auto,
+                    "always" => ColorChoice::Always,
+                    "never" => ColorChoice::Never,
+                    _ => {
+                        return Err(ConfigError::InvalidValue {
+                            flag: flag.to_string(),
+                            value,
+                        })
+                    }
+                })
+            }
+            //a thread per file is already more than enough, and asking the os
+            //for a million of them only ends in an abort
+            "threads" => match number(flag, &value)? {
+                0 | 1025.. => {
+                    return Err(ConfigError::InvalidValue {
+                        flag: flag.to_string(),
+                        value,
+                    })
+                }
+                threads => self.threads = Some(threads),
+            },
+            "json" => self.json = true,
+            "show-pattern" => self.show_pattern = true,
+            "replace" => self.replace = Some(value),
+            "in-place" => self.in_place = Some(value),
+            "search-zip" => self.search_zip = true,
+            //the last of -a and --binary-files wins
+            "text" => self.binary_files = BinaryFiles::Text,
+            "binary-files" => {
+                self.binary_files = match value.as_str() {
+                    "binary" => BinaryFiles::Binary,
+                    "text" => BinaryFiles::Text,
+                    "without-match" => BinaryFiles::WithoutMatch,
+                    _ => {
+                        return Err(ConfigError::InvalidValue {
+                            flag: flag.to_string(),
+                            value,
+                        })
+                    }
+                }
+            }
+            "include" => self.include.push(value),
+            "exclude" => self.exclude.push(value),
+            "no-config" => {} //already dealt with, see Config::build
+            "help" => return Err(ConfigError::Help),
+            "version" => return Err(ConfigError::Version),
+            _ => return Err(ConfigError::UnknownFlag(flag.to_string())),
+        }
+        Ok(())
+    }
+
+    //flags and positional args, in the order they're given
+    fn parse_args<'a>(&mut self, args: impl IntoIterator<Item = &'a String>) -> Result<(), ConfigError> {
+        for arg in split_args(args)? {
+            match arg {
+                Arg::Positional(arg) => self.positional.push(arg),
+                Arg::Flag { long, flag, value } => self.apply(&long, &flag, value)?,
+            }
+        }
+        Ok(())
+    }
+
+    //one flag per line like ripgrep's config file, so a value with spaces
+    //in it (--include=my notes/*) doesn't need quoting
+    fn config_file(&mut self, path: &Path) -> Result<(), ConfigError> {
+        let error = |msg: String| ConfigError::ConfigFile {
+            path: path.display().to_string(),
+            msg,
+        };
+        let contents = fs::read_to_string(path).map_err(|err| error(err.to_string()))?;
+        let args: Vec<String> = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from)
+            .collect();
+
+        for arg in split_args(&args).map_err(|err| error(err.to_string()))? {
+            match arg {
+                //patterns and paths only make sense on the command line
+                Arg::Positional(arg) => return Err(error(format!("'{arg}' isn't an option"))),
+                Arg::Flag { long, flag, .. } if long == "regexp" || long == "file" => {
+                    return Err(error(format!("{flag} only works on the command line")))
+                }
+                Arg::Flag { long, flag, value } => self.apply(&long, &flag, value).map_err(|err| error(err.to_string()))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+//an argument, once split_args has worked out whether it's a flag
+enum Arg {
+    Flag { long: String, flag: String, value: Option<String> }, //flag is what the user typed, for error messages
+    Positional(String),
+}
+
+//splits the arguments into flags and positional args, in the order they're
+//given. a flag's value comes with it, so -e --no-config is a pattern and
+//not a flag
+fn split_args<'a>(args: impl IntoIterator<Item = &'a String>) -> Result<Vec<Arg>, ConfigError> {
+    let mut split = Vec::new();
+    let mut iter = args.into_iter();
+    let mut flags_done = false;
+
+    while let Some(arg) = iter.next() {
+        //"-" on its own is a path (stdin to most tools), not a flag
+        if flags_done || arg == "-" || !arg.starts_with('-') {
+            split.push(Arg::Positional(arg.clone())); //clone so we don't have lifetime issues
+            continue;
+        }
+        if arg == "--" {
+            flags_done = true;
+            continue;
+        }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            //--context 2 and --context=2 both work
+            let (long, inline) = match long.split_once('=') {
+                Some((long, value)) => (long, Some(value.to_string())),
+                None => (long, None),
+            };
+            let value = if let Some(default) = default_value(long) {
+                //an optional value has to be attached with =, or it'd eat the pattern
+                Some(inline.unwrap_or_else(|| default.to_string()))
+            } else if takes_value(long) {
+                match inline.or_else(|| iter.next().cloned()) {
+                    Some(value) => Some(value),
+                    None => return Err(ConfigError::MissingValue(arg.clone())),
+                }
+            } else if inline.is_some() {
+                return Err(ConfigError::InvalidValue {
+                    flag: format!("--{long}"),
+                    value: inline.unwrap_or_default(),
+                });
+            } else {
+                None
+            };
+            split.push(Arg::Flag {
+                long: long.to_string(),
+                flag: format!("--{long}"),
+                value,
+            });
+            continue;
+        }
+
+        //a cluster of short flags like -inv. a flag that takes a value
+        //eats the rest of the cluster (-C2, -efoo) or else the next argument
+        let cluster = &arg[1..];
+        for (i, short) in cluster.char_indices() {
+            let flag = format!("-{short}");
+            let long = match long_name(short) {
+                Some(long) => long,
+                None => return Err(ConfigError::UnknownFlag(flag)),
+            };
+
+            if takes_value(long) {
+                let rest = &cluster[i + short.len_utf8()..];
+                let value = if !rest.is_empty() {
+                    rest.to_string()
+                } else {
+                    match iter.next() {
+                        Some(value) => value.clone(),
+                        None => return Err(ConfigError::MissingValue(flag)),
+                    }
+                };
+                split.push(Arg::Flag {
+                    long: long.to_string(),
+                    flag,
+                    value: Some(value),
+                });
+                break;
+            }
+            split.push(Arg::Flag {
+                long: long.to_string(),
+                flag,
+                value: None,
+            });
+        }
+    }
+    Ok(split)
+}
+
+//--no-config has to be found before anything gets parsed, since everything
+//from the config file goes before the command line
+fn no_config(args: &[String]) -> Result<bool, ConfigError> {
+    let args = split_args(args.iter().skip(1))?;
+    Ok(args.iter().any(|arg| matches!(arg, Arg::Flag { long, .. } if long == "no-config")))
+}
+
+//$MINIGREP_CONFIG, and if that isn't set the usual place for config files.
+//only a file that was asked for by name has to exist
+fn config_path() -> Option<PathBuf> {
+    match env::var_os("MINIGREP_CONFIG") {
+        Some(path) if path.is_empty() => None, //MINIGREP_CONFIG= turns it off
+        Some(path) => Some(PathBuf::from(path)),
+        None => {
+            let dir = match env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+                Some(dir) => PathBuf::from(dir),
+                None => PathBuf::from(env::var_os("HOME")?).join(".config"),
+            };
+            let path = dir.join("minigrep").join("config");
+            path.is_file().then_some(path)
+        }
+    }
+}
+
+//one pattern per line, like grep -f. an empty line is a pattern that
+//matches everything and an empty file has no patterns, so it matches nothing
+fn read_patterns(path: &str) -> Result<Vec<String>, ConfigError> {
+    let contents = if path == "-" {
+        let mut contents = String::new();
+        io::stdin().read_to_string(&mut contents).map(|_| contents)
+    } else {
+        fs::read_to_string(path)
+    };
+    let contents = contents.map_err(|err| ConfigError::PatternFile {
+        path: path.to_string(),
+        msg: err.to_string(),
+    })?;
+
+    //lines() drops the \r of \r\n too, so windows pattern files work
+    Ok(contents.lines().map(String::from).collect())
+}
+
+fn number(flag: &str, value: &str) -> Result<usize, ConfigError> {
+    value.parse().map_err(|_| ConfigError::InvalidValue {
+        flag: flag.to_string(),
+        value: value.to_string(),
+    })
+}
+
+impl Config {
+    //doesnt take a reference to self so is Config::build()
+    //note: we call it build() bc people expect new() functions
+    //to never fail
+
+    //good practice to make the build return a Result
+    pub fn build(args: &[String]) -> Result<Self, ConfigError> {
+        //takes in a slice of a sequence containing Strings
+        //returns a Result that is either a Config in the Ok case
+        //or a ConfigError saying what was wrong with the arguments
+
+        //note: as in C, a program by default takes its own name as
+        //its first command line argument, i.e. args[0]
+        let mut parsed = Parsed::default();
+
+        if !no_config(args)? {
+            if let Some(path) = config_path() {
+                parsed.config_file(&path)?;
+            }
+        }
+        Config::from_parsed(parsed, args, &Environment::read())
+    }
+
+    //just the command line, without the config file or the environment, so
+    //tests don't depend on the machine they run on
+    #[cfg(test)]
+    pub(crate) fn from_args(args: &[String]) -> Result<Self, ConfigError> {
+        Config::from_parsed(Parsed::default(), args, &Environment::default())
+    }
+
+    //the command line on top of whatever defaults parsed already has
+    fn from_parsed(mut parsed: Parsed, args: &[String], environment: &Environment) -> Result<Self, ConfigError> {
+        //the environment beats the config file, so it goes in between.
+        //example use:  IGNORE_CASE=1 cargo run WHO poem.txt
+        if environment.ignore_case {
+            parsed.ignore_case = Some(true);
+            parsed.smart_case = false;
+        }
+        parsed.parse_args(args.iter().skip(1))?;
+
+        //without -e the first positional argument is the pattern
+        let mut positional = parsed.positional.into_iter();
+        let patterns = if parsed.patterns.is_empty() && !parsed.pattern_file {
+            match positional.next() {
+                Some(pattern) => vec![pattern],
+                None => return Err(ConfigError::MissingPattern),
+            }
+        } else {
+            parsed.patterns
+        };
+        //no paths at all means read from stdin, same as passing "-"
+        let mut paths: Vec<String> = positional.collect();
+        if paths.is_empty() {
+            paths.push("-".to_string());
+        }
+
+        //json describes every matching line, so it doesn't go with the
+        //modes that print file names or counts instead
+        let mode = if parsed.quiet {
+            OutputMode::Quiet
+        } else {
+            parsed.mode.unwrap_or(OutputMode::Lines)
+        };
+        let other = match mode {
+            OutputMode::Count => Some("--count"),
+            OutputMode::FilesWithMatches => Some("--files-with-matches"),
+            OutputMode::FilesWithoutMatch => Some("--files-without-match"),
+            _ => None,
+        };
+        if let (true, Some(other)) = (parsed.json, other) {
+            return Err(ConfigError::Conflict("--json", other));
+        }
+        if parsed.json && parsed.replace.is_some() {
+            return Err(ConfigError::Conflict("--json", "--replace"));
+        }
+        //rewriting files is all about the matches, so -v doesn't make sense
+        if parsed.in_place.is_some() {
+            if parsed.replace.is_none() {
+                return Err(ConfigError::Requires("--in-place", "--replace"));
+            }
+            if parsed.invert_match {
+                return Err(ConfigError::Conflict("--in-place", "--invert-match"));
+            }
+            //a .gz would come back decompressed
+            if parsed.search_zip {
+                return Err(ConfigError::Conflict("--in-place", "--search-zip"));
+            }
+            //the rewriting goes line by line
+            if parsed.multiline {
+                return Err(ConfigError::Conflict("--in-place", "--multiline"));
+            }
+            //the new file would have to be encoded back the same way
+            if parsed.encoding != Encoding::Utf8 {
+                return Err(ConfigError::Conflict("--in-place", "--encoding"));
+            }
+        }
+
+        //following a file means it never ends, so nothing that needs its end
+        if parsed.follow {
+            let other = match mode {
+                OutputMode::Count => Some("--count"),
+                OutputMode::FilesWithoutMatch => Some("--files-without-match"),
+                _ if parsed.in_place.is_some() => Some("--in-place"),
+                _ if parsed.multiline => Some("--multiline"),
+                _ if parsed.search_zip => Some("--search-zip"),
+                _ => None,
+            };
+            if let Some(other) = other {
+                return Err(ConfigError::Conflict("--follow", other));
+            }
+        }
+
+        //the index has what's in the files on disk, not what's in them decompressed
+        if parsed.index && parsed.search_zip {
+            return Err(ConfigError::Conflict("--index", "--search-zip"));
+        }
+
+        //-w and -x are assertions around a regex, which a fuzzy pattern isn't
+        if parsed.fuzzy.is_some() {
+            if parsed.word_regexp {
+                return Err(ConfigError::Conflict("--fuzzy", "--word-regexp"));
+            }
+            if parsed.line_regexp {
+                return Err(ConfigError::Conflict("--fuzzy", "--line-regexp"));
+            }
+        }
+
+        //the header names the column for csv and tsv, and json lines has
+        //no header so it needs a path instead
+        let field = match (parsed.records, parsed.field, parsed.path) {
+            (None, None, None) => None,
+            (None, Some(_), _) => return Err(ConfigError::Requires("--field", "--csv")),
+            (None, None, Some(_)) => return Err(ConfigError::Requires("--path", "--jsonl")),
+            (Some("--jsonl"), Some(_), _) => return Err(ConfigError::Conflict("--jsonl", "--field")),
+            (Some("--jsonl"), None, None) => return Err(ConfigError::Requires("--jsonl", "--path")),
+            (Some("--jsonl"), None, Some(path)) => match Field::jsonl(&path) {
+                Some(field) => Some(field),
+                None => {
+                    return Err(ConfigError::InvalidValue {
+                        flag: "--path".to_string(),
+                        value: path,
+                    })
+                }
+            },
+            (Some(records), _, Some(_)) => return Err(ConfigError::Conflict(records, "--path")),
+            (Some(records), None, None) => return Err(ConfigError::Requires(records, "--field")),
+            (Some(records), Some(name), None) => match records {
+                "--csv" => Some(Field::csv(&name)),
+                _ => Some(Field::tsv(&name)),
+            },
+        };
+        if let Some(records) = field.as_ref().and(parsed.records) {
+            //a record can be several lines, and only the lines that are
+            //whole records get searched as they're read
+            let other = match () {
+                _ if parsed.in_place.is_some() => Some("--in-place"),
+                _ if parsed.multiline => Some("--multiline"),
+                _ if parsed.follow => Some("--follow"),
+                _ => None,
+            };
+            if let Some(other) = other {
+                return Err(ConfigError::Conflict(records, other));
+            }
+        }
+
+        //smart case looks at every pattern, so -e foo -e Bar is case sensitive
+        let ignore_case = if parsed.smart_case {
+            !patterns.iter().any(|pattern| casefold::has_uppercase(pattern))
+        } else {
+            parsed.ignore_case.unwrap_or(false)
+        };
+
+        Ok(Config {
+            patterns,
+            paths,
+            ignore_case,
+            word_regexp: parsed.word_regexp,
+            line_regexp: parsed.line_regexp,
+            fixed_strings: parsed.fixed_strings,
+            invert_match: parsed.invert_match,
+            mode,
+            no_messages: parsed.no_messages,
+            include: parsed.include,
+            exclude: parsed.exclude,
+            line_number: parsed.line_number,
+            byte_offset: parsed.byte_offset,
+            before_context: parsed.before_context,
+            after_context: parsed.after_context,
+            color: parsed.color.unwrap_or(ColorChoice::Auto),
+            threads: parsed.threads,
+            json: parsed.json,
+            show_pattern: parsed.show_pattern,
+            replace: parsed.replace,
+            in_place: parsed.in_place,
+            search_zip: parsed.search_zip,
+            binary_files: parsed.binary_files,
+            multiline: parsed.multiline,
+            follow: parsed.follow,
+            index: parsed.index,
+            fuzzy: parsed.fuzzy,
+            field,
+            encoding: parsed.encoding,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tempdir::TempDir;
+
+    fn build(args: &[&str]) -> Result<Config, ConfigError> {
+        let mut all = vec!["minigrep".to_string()];
+        all.extend(args.iter().map(|s| s.to_string()));
+        Config::from_args(&all)
+    }
+
+    #[test]
+    fn positional_pattern_and_paths() {
+        let config = build(&["fn", "src", "--include", "*.rs", "tests", "--exclude=target"]).unwrap();
+        assert_eq!(vec!["fn"], config.patterns);
+        assert_eq!(vec!["src", "tests"], config.paths);
+        assert_eq!(vec!["*.rs"], config.include);
+        assert_eq!(vec!["target"], config.exclude);
+        assert_eq!(OutputMode::Lines, config.mode);
+    }
+
+    #[test]
+    fn combined_short_flags_and_values() {
+        let config = build(&["-invC2", "-e", "foo", "-ebar", "poem.txt"]).unwrap();
+        assert!(config.ignore_case && config.invert_match && config.line_number);
+        assert_eq!((2, 2), (config.before_context, config.after_context));
+        assert_eq!(vec!["foo", "bar"], config.patterns);
+        assert_eq!(vec!["poem.txt"], config.paths);
+
+        let config = build(&["-xF", "a.b"]).unwrap();
+        assert!(config.line_regexp && config.fixed_strings && !config.word_regexp);
+
+        let config = build(&["-cA", "1", "x", "y"]).unwrap();
+        assert_eq!(OutputMode::Count, config.mode);
+        assert_eq!((0, 1), (config.before_context, config.after_context));
+    }
+
+    #[test]
+    fn double_dash_ends_flags() {
+        let config = build(&["-l", "--", "-v", "-"]).unwrap();
+        assert_eq!(vec!["-v"], config.patterns);
+        assert_eq!(vec!["-"], config.paths);
+        assert!(!config.invert_match);
+        assert_eq!(OutputMode::FilesWithMatches, config.mode);
+    }
+
+    #[test]
+    fn usage_errors() {
+        assert_eq!(Err(ConfigError::MissingPattern), build(&["-i"]));
+        assert_eq!(vec!["-"], build(&["fn"]).unwrap().paths);
+        assert_eq!(Err(ConfigError::UnknownFlag("-k".to_string())), build(&["-ik", "fn", "src"]));
+        assert_eq!(Err(ConfigError::UnknownFlag("--nope".to_string())), build(&["--nope", "fn", "src"]));
+        assert_eq!(Err(ConfigError::MissingValue("-e".to_string())), build(&["-e"]));
+        assert!(matches!(build(&["-C", "two", "fn", "src"]), Err(ConfigError::InvalidValue { .. })));
+        assert!(matches!(build(&["-j0", "fn"]), Err(ConfigError::InvalidValue { .. })));
+        assert!(matches!(build(&["-j", "99999999999", "fn"]), Err(ConfigError::InvalidValue { .. })));
+        assert_eq!(Some(1024), build(&["-j1024", "fn"]).unwrap().threads);
+        assert_eq!(Err(ConfigError::Conflict("--json", "--count")), build(&["--json", "-c", "fn"]));
+        assert_eq!(Err(ConfigError::Help), build(&["fn", "--help"]));
+        assert_eq!(Err(ConfigError::Version), build(&["-V"]));
+    }
+
+    #[test]
+    fn config_file_defaults() {
+        let dir = TempDir::new("config");
+        let path = dir.join("config");
+        fs::write(&path, "# defaults\n--line-number\n\n  --exclude=target  \n-C\n2\n--color=always\n").unwrap();
+        let with_defaults = |args: &[&str]| {
+            let mut parsed = Parsed::default();
+            parsed.config_file(&path)?;
+            let all: Vec<String> = ["minigrep"].iter().chain(args).map(|s| s.to_string()).collect();
+            Config::from_parsed(parsed, &all, &Environment::default())
+        };
+
+        let config = with_defaults(&["Fn", "src", "--exclude", "*.md"]).unwrap();
+        assert!(config.line_number);
+        assert_eq!(vec!["target", "*.md"], config.exclude); //lists add up
+        assert_eq!((2, 2, ColorChoice::Always), (config.before_context, config.after_context, config.color));
+        //the command line beats the file
+        let config = with_defaults(&["-C0", "--color=never", "--case-sensitive", "fn"]).unwrap();
+        assert_eq!((0, ColorChoice::Never, false), (config.after_context, config.color, config.ignore_case));
+
+        fs::write(&path, "-n\nfn\n").unwrap();
+        assert!(matches!(with_defaults(&["fn"]), Err(ConfigError::ConfigFile { msg, .. }) if msg == "'fn' isn't an option"));
+        //a pattern in the config file would turn the pattern on the command line into a path
+        for pattern in ["-efoo", "--regexp=foo", "-f\npatterns.txt"] {
+            fs::write(&path, pattern).unwrap();
+            assert!(matches!(with_defaults(&["fn"]), Err(ConfigError::ConfigFile { msg, .. }) if msg.ends_with("only works on the command line")));
+        }
+        fs::write(&path, "--colour=sometimes\n").unwrap();
+        assert!(matches!(with_defaults(&["fn"]), Err(ConfigError::ConfigFile { .. })));
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(with_defaults(&["fn"]), Err(ConfigError::ConfigFile { .. })));
+
+        //--no-config is a flag like any other as far as parsing goes
+        assert!(build(&["--no-config", "fn"]).is_ok());
+    }
+
+    #[test]
+    fn no_config_flag() {
+        let no_config = |args: &[&str]| {
+            let all: Vec<String> = ["minigrep"].iter().chain(args).map(|s| s.to_string()).collect();
+            no_config(&all).unwrap()
+        };
+        assert!(no_config(&["-n", "--no-config", "fn"]));
+        //the value of a flag, or a positional arg, that only looks like it
+        assert!(!no_config(&["-e", "--no-config", "file"]));
+        assert!(!no_config(&["--regexp", "--no-config"]));
+        assert!(!no_config(&["-C", "2", "--", "--no-config"]));
+    }
+
+    #[test]
+    fn flags_beat_environment() {
+        let environment = Environment { ignore_case: true };
+        let with_environment = |args: &[&str]| {
+            let all: Vec<String> = ["minigrep"].iter().chain(args).map(|s| s.to_string()).collect();
+            Config::from_parsed(Parsed::default(), &all, &environment).unwrap()
+        };
+        let from_env = with_environment(&["fn", "src"]);
+        let from_flag = with_environment(&["--case-sensitive", "fn", "src"]);
+
+        assert!(from_env.ignore_case);
+        assert!(!from_flag.ignore_case);
+    }
+
+    #[test]
+    fn patterns_from_files() {
+        let dir = TempDir::new("patterns");
+        let path = dir.join("patterns.txt");
+        let file = path.to_str().unwrap();
+        fs::write(&path, "evil.example\r\n10.6.6.6\n").unwrap();
+        let config = build(&["-e", "first", "-f", file, "-e", "last", "logs"]).unwrap();
+        fs::write(&path, "").unwrap();
+        let empty = build(&[&format!("--file={file}"), "logs"]).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(vec!["first", "evil.example", "10.6.6.6", "last"], config.patterns);
+        assert_eq!(vec!["logs"], config.paths);
+        assert!(empty.patterns.is_empty());
+        assert_eq!(vec!["logs"], empty.paths);
+        assert!(matches!(build(&["-f", "/no/such/file"]), Err(ConfigError::PatternFile { .. })));
+    }
+
+    #[test]
+    fn replacing() {
+        let config = build(&["--replace", "$1", "(a)b"]).unwrap();
+        assert_eq!((Some("$1".to_string()), None), (config.replace, config.in_place));
+
+        let config = build(&["--in-place", "--replace=x", "a", "notes.txt"]).unwrap();
+        assert_eq!(Some(".bak".to_string()), config.in_place);
+        assert_eq!(vec!["notes.txt"], config.paths);
+        assert_eq!(Some("".to_string()), build(&["--in-place=", "--replace=x", "a"]).unwrap().in_place);
+
+        assert_eq!(Err(ConfigError::Requires("--in-place", "--replace")), build(&["--in-place", "a"]));
+        assert_eq!(Err(ConfigError::Conflict("--in-place", "--invert-match")), build(&["--in-place", "--replace=x", "-v", "a"]));
+        assert_eq!(Err(ConfigError::Conflict("--json", "--replace")), build(&["--json", "--replace=x", "a"]));
+        assert_eq!(Err(ConfigError::Conflict("--in-place", "--search-zip")), build(&["-z", "--in-place", "--replace=x", "a"]));
+    }
+
+    #[test]
+    fn following() {
+        assert!(build(&["--follow", "-q", "ready", "app.log"]).unwrap().follow);
+        assert_eq!(Err(ConfigError::Conflict("--follow", "--count")), build(&["--follow", "-c", "a", "app.log"]));
+        assert_eq!(Err(ConfigError::Conflict("--follow", "--multiline")), build(&["-U", "--follow", "a", "app.log"]));
+    }
+
+    #[test]
+    fn fuzzy() {
+        assert_eq!(Some(2), build(&["--fuzzy", "2", "recieve", "ocr.txt"]).unwrap().fuzzy);
+        assert_eq!(Some(0), build(&["--fuzzy=0", "receive"]).unwrap().fuzzy);
+        assert!(matches!(build(&["--fuzzy", "two", "receive"]), Err(ConfigError::InvalidValue { .. })));
+        assert_eq!(Err(ConfigError::Conflict("--fuzzy", "--word-regexp")), build(&["--fuzzy", "1", "-w", "receive"]));
+    }
+
+    #[test]
+    fn records() {
+        let config = build(&["--csv", "--field", "city", "Paris", "people.csv"]).unwrap();
+        assert_eq!(Some(Field::csv("city")), config.field);
+        assert_eq!(Some(Field::tsv("id")), build(&["--tsv", "--field=id", "42"]).unwrap().field);
+        assert_eq!(Field::jsonl(".user.name"), build(&["--jsonl", "--path", ".user.name", "ana"]).unwrap().field);
+        assert_eq!(Err(ConfigError::Requires("--csv", "--field")), build(&["--csv", "Paris"]));
+        assert_eq!(Err(ConfigError::Requires("--path", "--jsonl")), build(&["--path", ".a", "x"]));
+        assert_eq!(Err(ConfigError::Conflict("--csv", "--jsonl")), build(&["--csv", "--jsonl", "--path", ".a", "x"]));
+        assert_eq!(Err(ConfigError::Conflict("--jsonl", "--field")), build(&["--jsonl", "--field", "a", "x"]));
+        assert!(matches!(build(&["--jsonl", "--path", "user", "x"]), Err(ConfigError::InvalidValue { .. })));
+        assert_eq!(Err(ConfigError::Conflict("--tsv", "--multiline")), build(&["-U", "--tsv", "--field=id", "x"]));
+    }
+
+    #[test]
+    fn encoding() {
+        assert_eq!(Encoding::Utf8, build(&["error", "app.log"]).unwrap().encoding);
+        assert_eq!(Encoding::Utf16Le, build(&["-E", "utf-16le", "error", "app.log"]).unwrap().encoding);
+        assert_eq!(Encoding::Latin1, build(&["--encoding=ISO-8859-1", "café"]).unwrap().encoding);
+        assert!(matches!(build(&["--encoding", "ebcdic", "a"]), Err(ConfigError::InvalidValue { .. })));
+        assert_eq!(
+            Err(ConfigError::Conflict("--in-place", "--encoding")),
+            build(&["--in-place", "--replace=x", "-E", "latin1", "a"])
+        );
+    }
+
+    #[test]
+    fn smart_case() {
+        assert!(build(&["-S", "fn", "src"]).unwrap().ignore_case);
+        assert!(!build(&["-S", "Fn", "src"]).unwrap().ignore_case);
+        assert!(build(&["-S", r"\Wfn\S", "src"]).unwrap().ignore_case);
+        assert!(!build(&["--smart-case", "-e", "fn", "-e", "Option"]).unwrap().ignore_case);
+        //whichever case flag comes last wins
+        assert!(build(&["-S", "-i", "Fn", "src"]).unwrap().ignore_case);
+        assert!(!build(&["-i", "-S", "Fn", "src"]).unwrap().ignore_case);
+    }
+
+    #[test]
+    fn quiet_beats_other_modes() {
+        assert_eq!(OutputMode::Quiet, build(&["-q", "-c", "fn", "src"]).unwrap().mode);
+        assert_eq!(OutputMode::Quiet, build(&["-lq", "fn", "src"]).unwrap().mode);
+        assert_eq!(OutputMode::FilesWithoutMatch, build(&["-l", "-L", "fn", "src"]).unwrap().mode);
+    }
+
+    #[test]
+    fn color_choice() {
+        assert_eq!(ColorChoice::Auto, build(&["fn"]).unwrap().color);
+        assert_eq!(ColorChoice::Auto, build(&["--color", "fn"]).unwrap().color);
+        assert_eq!(ColorChoice::Never, build(&["--colour=never", "fn"]).unwrap().color);
+        assert!(matches!(build(&["--color=sometimes", "fn"]), Err(ConfigError::InvalidValue { .. })));
+    }
+}
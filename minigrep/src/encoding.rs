@@ -0,0 +1,246 @@
+use std::io::{self, BufRead, Read};
+
+//everything past this point works on utf-8, so text in any other encoding
+//gets turned into utf-8 as it's read. a file that starts with a byte order
+//mark says what it's in, windows tools like to write utf-16 with one.
+//anything else is in whatever --encoding says, utf-8 unless told otherwise.
+//lines are counted after decoding, so their numbers are the same as in the
+//file, but byte offsets are into the utf-8 text
+
+/// What text is encoded in.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,      //iso-8859-1, every byte is the code point with that number
+    Windows1252, //latin-1 with printable chars where it has the C1 controls
+}
+
+impl Encoding {
+    /// The encoding a name like "utf-16le" or "latin1" is for, ignoring
+    /// case and the dash or lack of it.
+    pub fn from_name(name: &str) -> Option<Encoding> {
+        let encoding = match name.to_ascii_lowercase().replace(['-', '_'], "").as_str() {
+            "utf8" => Encoding::Utf8,
+            "utf16le" => Encoding::Utf16Le,
+            "utf16be" => Encoding::Utf16Be,
+            "latin1" | "iso88591" => Encoding::Latin1,
+            "windows1252" | "cp1252" => Encoding::Windows1252,
+            _ => return None,
+        };
+        Some(encoding)
+    }
+}
+
+/// The encoding the byte order mark at the start of `head` is for, and
+/// how long the mark is.
+pub fn bom(head: &[u8]) -> Option<(Encoding, usize)> {
+    if head.starts_with(&[0xef, 0xbb, 0xbf]) {
+        Some((Encoding::Utf8, 3))
+    } else if head.starts_with(&[0xff, 0xfe]) {
+        Some((Encoding::Utf16Le, 2))
+    } else if head.starts_with(&[0xfe, 0xff]) {
+        Some((Encoding::Utf16Be, 2))
+    } else {
+        None
+    }
+}
+
+/// A whole file as utf-8 if it has a byte order mark, and as it is if not.
+pub fn decode_bom(bytes: Vec<u8>) -> io::Result<Vec<u8>> {
+    match bom(&bytes) {
+        None => Ok(bytes),
+        Some((Encoding::Utf8, len)) => Ok(bytes[len..].to_vec()),
+        Some((encoding, len)) => {
+            let mut utf8 = Vec::with_capacity(bytes.len());
+            Decoder::new(&bytes[len..], encoding).read_to_end(&mut utf8)?;
+            Ok(utf8)
+        }
+    }
+}
+
+//the unicode chars windows-1252 has at 0x80 to 0x9f. the five it leaves
+//out are the C1 controls, same as in latin-1
+const WINDOWS_1252: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž', '\u{8f}', //
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}', 'ž', 'Ÿ',
+];
+
+/// Turns text in some other encoding into utf-8 as it's read. Anything
+/// that can't be decoded (a lone surrogate, a utf-16 file with an odd
+/// number of bytes) comes out as U+FFFD.
+pub struct Decoder<R: BufRead> {
+    inner: R,
+    encoding: Encoding,
+    out: Vec<u8>,
+    pos: usize,        //how much of out has been read
+    odd: Option<u8>,   //the first byte of a utf-16 unit whose second is in the next read
+    high: Option<u16>, //a high surrogate, waiting for the low one after it
+    done: bool,
+}
+
+impl<R: BufRead> Decoder<R> {
+    /// `encoding` is what `inner` is in, after any byte order mark.
+    pub fn new(inner: R, encoding: Encoding) -> Decoder<R> {
+        Decoder {
+            inner,
+            encoding,
+            out: Vec::new(),
+            pos: 0,
+            odd: None,
+            high: None,
+            done: false,
+        }
+    }
+
+    //decodes the next buffer of input
+    fn decode(&mut self) -> io::Result<()> {
+        self.out.clear();
+        self.pos = 0;
+        let buf = self.inner.fill_buf()?;
+        if buf.is_empty() {
+            //whatever was left over was never going to be a char
+            if self.high.take().is_some() {
+                push(&mut self.out, char::REPLACEMENT_CHARACTER);
+            }
+            if self.odd.take().is_some() {
+                push(&mut self.out, char::REPLACEMENT_CHARACTER);
+            }
+            self.done = true;
+            return Ok(());
+        }
+
+        let len = buf.len();
+        let mut chunk = buf.iter().copied();
+        match self.encoding {
+            Encoding::Utf8 => self.out.extend(chunk),
+            Encoding::Latin1 => chunk.for_each(|byte| push(&mut self.out, byte as char)),
+            Encoding::Windows1252 => chunk.for_each(|byte| {
+                let c = match byte {
+                    0x80..=0x9f => WINDOWS_1252[byte as usize - 0x80],
+                    _ => byte as char,
+                };
+                push(&mut self.out, c)
+            }),
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                while let Some(first) = self.odd.take().or_else(|| chunk.next()) {
+                    let Some(second) = chunk.next() else {
+                        self.odd = Some(first);
+                        break;
+                    };
+                    let unit = match self.encoding {
+                        Encoding::Utf16Le => u16::from_le_bytes([first, second]),
+                        _ => u16::from_be_bytes([first, second]),
+                    };
+                    utf16(&mut self.out, &mut self.high, unit);
+                }
+            }
+        }
+        self.inner.consume(len);
+        Ok(())
+    }
+}
+
+fn push(out: &mut Vec<u8>, c: char) {
+    out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+}
+
+//a char outside the basic plane is two units, a high surrogate and then a low one
+fn utf16(out: &mut Vec<u8>, high: &mut Option<u16>, unit: u16) {
+    if let Some(first) = high.take() {
+        if let 0xdc00..=0xdfff = unit {
+            let c = 0x10000 + ((first as u32 - 0xd800) << 10) + (unit as u32 - 0xdc00);
+            push(out, char::from_u32(c).unwrap_or(char::REPLACEMENT_CHARACTER));
+            return;
+        }
+        push(out, char::REPLACEMENT_CHARACTER);
+    }
+    match unit {
+        0xd800..=0xdbff => *high = Some(unit),
+        0xdc00..=0xdfff => push(out, char::REPLACEMENT_CHARACTER),
+        _ => push(out, char::from_u32(unit as u32).unwrap_or(char::REPLACEMENT_CHARACTER)),
+    }
+}
+
+impl<R: BufRead> Read for Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let n = buf.len().min(available.len());
+        buf[..n].copy_from_slice(&available[..n]);
+        self.consume(n);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Decoder<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        //a read can decode to nothing, like one that's only half a utf-16 unit
+        while self.pos == self.out.len() && !self.done {
+            self.decode()?;
+        }
+        Ok(&self.out[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.out.len());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decode(bytes: &[u8], encoding: Encoding) -> String {
+        let mut out = String::new();
+        Decoder::new(bytes, encoding).read_to_string(&mut out).unwrap();
+        out
+    }
+
+    fn utf16le(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    #[test]
+    fn decoding() {
+        assert_eq!("héllo 𝄞\r\nbye", decode(&utf16le("héllo 𝄞\r\nbye"), Encoding::Utf16Le));
+        let be: Vec<u8> = "a€\n".encode_utf16().flat_map(u16::to_be_bytes).collect();
+        assert_eq!("a€\n", decode(&be, Encoding::Utf16Be));
+        assert_eq!("café ÿ", decode(b"caf\xe9 \xff", Encoding::Latin1));
+        assert_eq!(
+            "“quoted” €5 \u{81}",
+            decode(b"\x93quoted\x94 \x805 \x81", Encoding::Windows1252)
+        );
+        //a lone surrogate, then an odd byte at the end
+        assert_eq!(
+            "a\u{fffd}b\u{fffd}",
+            decode(&[b'a', 0, 0x00, 0xd8, b'b', 0, b'c'], Encoding::Utf16Le)
+        );
+    }
+
+    #[test]
+    fn units_split_between_reads() {
+        //a BufReader with a 3 byte buffer cuts every other unit, and the pair, in half
+        let bytes = utf16le("x𝄞yz\n");
+        let reader = io::BufReader::with_capacity(3, &bytes[..]);
+        let mut out = String::new();
+        Decoder::new(reader, Encoding::Utf16Le)
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!("x𝄞yz\n", out);
+    }
+
+    #[test]
+    fn byte_order_marks() {
+        assert_eq!(Some((Encoding::Utf16Le, 2)), bom(b"\xff\xfeh\0"));
+        assert_eq!(Some((Encoding::Utf8, 3)), bom(b"\xef\xbb\xbfhi"));
+        assert_eq!(None, bom(b"hi"));
+        let mut file = vec![0xff, 0xfe];
+        file.extend(utf16le("error\n"));
+        assert_eq!(b"error\n".to_vec(), decode_bom(file).unwrap());
+        assert_eq!(Some(Encoding::Windows1252), Encoding::from_name("CP1252"));
+        assert_eq!(Some(Encoding::Utf16Be), Encoding::from_name("utf16-be"));
+        assert_eq!(None, Encoding::from_name("ebcdic"));
+    }
+}
@@ -0,0 +1,345 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::Duration;
+
+use crate::literal;
+use crate::printer::Printer;
+use crate::walk::WalkError;
+use crate::{display_name, search_file, search_with, Config, Regex, Stats, Tally};
+
+//--follow: like tail -f piped into grep, except that the file is searched
+//from the start and keeps being searched as it grows. logs get truncated
+//(copytruncate) or moved away for a new one (rotation), and then the file
+//is searched again from its new start instead of stopping or waiting
+//forever on a file nobody writes to anymore
+
+//how long to wait at the end of the file before looking again
+const POLL: Duration = Duration::from_millis(250);
+
+/// Why a Follow ended its input, after which it's reading the file from
+/// its start again.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Restart {
+    Truncated, //the file got shorter than what was already read
+    Replaced,  //there's a different file at the path now
+}
+
+/// Reads a file that's still being written to: at the end of it, it waits
+/// for more instead of returning 0. A read only returns 0 when the file was
+/// truncated or replaced, once, and the next read starts on the new one.
+/// Only whole lines get handed out, so a line that's half written when the
+/// file is truncated or replaced is finished by what comes after the restart.
+pub struct Follow {
+    path: PathBuf,
+    file: File,
+    id: Option<(u64, u64)>, //device and inode, what tells a new file at the same path apart
+    read: u64,              //how far into the file we are
+    restart: Option<Restart>,
+    pending: Vec<u8>, //read from the file but not handed out yet, ends in the start of a line
+    poll: Duration,
+}
+
+impl Follow {
+    pub fn open(path: &Path) -> io::Result<Follow> {
+        let file = File::open(path)?;
+        let id = file_id(&file.metadata()?);
+        Ok(Follow {
+            path: path.to_path_buf(),
+            file,
+            id,
+            read: 0,
+            restart: None,
+            pending: Vec::new(),
+            poll: POLL,
+        })
+    }
+
+    /// Why the last read returned 0, if that's what it did.
+    pub fn take_restart(&mut self) -> Option<Restart> {
+        self.restart.take()
+    }
+
+    //what happened to the file, now that there's nothing more to read in it
+    fn check(&mut self) -> io::Result<Option<Restart>> {
+        let meta = match fs::metadata(&self.path) {
+            Ok(meta) => meta,
+            //moved away and the new one isn't there yet. whoever has the old
+            //one open can still write to it, so keep reading that meanwhile
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err),
+        };
+
+        if file_id(&meta) != self.id {
+            //whatever made it into the old file before the move comes first
+            if self.file.metadata()?.len() > self.read {
+                return Ok(None);
+            }
+            self.file = File::open(&self.path)?;
+            self.id = file_id(&self.file.metadata()?);
+            self.read = 0;
+            return Ok(Some(Restart::Replaced));
+        }
+        //a pipe has no length, only a file can be truncated. like with tail, one
+        //that's already grown back past where we were looks like it just grew
+        if meta.is_file() && meta.len() < self.read {
+            self.file.seek(SeekFrom::Start(0))?;
+            self.read = 0;
+            return Ok(Some(Restart::Truncated));
+        }
+        Ok(None)
+    }
+}
+
+impl Read for Follow {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            //whole lines first. the rest waits for its newline, which might
+            //only come after a restart, so it's never searched in two pieces
+            if let Some(nl) = literal::memrchr(b'\n', &self.pending) {
+                let n = buf.len().min(nl + 1);
+                buf[..n].copy_from_slice(&self.pending[..n]);
+                self.pending.drain(..n);
+                return Ok(n);
+            }
+
+            let start = self.pending.len();
+            self.pending.resize(start + buf.len(), 0);
+            let read = self.file.read(&mut self.pending[start..]);
+            self.pending.truncate(start + read.as_ref().map_or(0, |&read| read));
+            let read = read?;
+            if read > 0 {
+                self.read += read as u64;
+                continue;
+            }
+            if let Some(restart) = self.check()? {
+                self.restart = Some(restart);
+                return Ok(0);
+            }
+            thread::sleep(self.poll);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn file_id(meta: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((meta.dev(), meta.ino()))
+}
+
+//no inodes, so only truncation gets noticed
+#[cfg(not(unix))]
+fn file_id(_meta: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Searches the files and then whatever gets added to them, every file on
+/// its own thread since none of them is ever done. Lines go to stdout as
+/// soon as they're found instead of in the order of the files, and this
+/// only returns once every file has stopped (-l, -q or an error) or the
+/// tally says to stop.
+pub fn search_files(files: Vec<Result<PathBuf, WalkError>>, re: &Regex, config: &Config, printer: &Printer, tally: &mut Tally) {
+    let (sender, receiver) = mpsc::channel::<(PathBuf, io::Result<Stats>)>();
+    for file in files {
+        let path = match file {
+            Ok(path) => path,
+            Err(err) => {
+                if !tally.record(Err((err.path, err.err)), config) {
+                    return;
+                }
+                continue;
+            }
+        };
+        let (re, config, sender) = (re.clone(), config.clone(), sender.clone());
+        let mut printer = printer.clone();
+        thread::spawn(move || follow_file(path, &re, &config, &mut printer, io::stdout(), &sender));
+    }
+    drop(sender); //so the loop ends once every thread has
+
+    //a thread that's still following its file when we return just goes
+    //away with the process
+    for (path, result) in receiver {
+        if !tally.record(result.map_err(|err| (path, err)), config) {
+            break;
+        }
+    }
+}
+
+//every search of the file (one more after each restart) gets reported on
+//its own, so -q can stop everything at the first match
+fn follow_file(
+    path: PathBuf,
+    re: &Regex,
+    config: &Config,
+    printer: &mut Printer,
+    out: impl Write,
+    sender: &Sender<(PathBuf, io::Result<Stats>)>,
+) {
+    let mut out = WholeLines(Vec::new(), out);
+    //stdin is never truncated or replaced, it just ends
+    if path == Path::new("-") {
+        let _ = sender.send((path.clone(), search_file(&path, re, config, printer, &mut out)));
+        return;
+    }
+
+    let mut follow = match Follow::open(&path) {
+        Ok(follow) => follow,
+        Err(err) => {
+            let _ = sender.send((path, Err(err)));
+            return;
+        }
+    };
+    let name = display_name(&path);
+    loop {
+        let result = search_with(&name, re, config, printer, &mut out, |searcher, sink| {
+            searcher.search_reader(re, BufReader::new(&mut follow), sink)
+        });
+        let restart = follow.take_restart();
+        let done = result.is_err() || restart.is_none();
+        if sender.send((path.clone(), result)).is_err() || done {
+            return;
+        }
+        //the same messages as tail
+        if !config.no_messages {
+            match restart {
+                Some(Restart::Truncated) => eprintln!("minigrep: {name}: file truncated"),
+                _ => eprintln!("minigrep: {name} has been replaced; following new file"),
+            }
+        }
+    }
+}
+
+//what a follow thread prints through: a line only goes out once it's
+//complete, and in one write (stdout locks for each), so lines of different
+//files don't end up mixed together
+struct WholeLines<W>(Vec<u8>, W);
+
+impl<W: Write> Write for WholeLines<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.extend_from_slice(buf);
+        if let Some(nl) = literal::memrchr(b'\n', &self.0) {
+            self.1.write_all(&self.0[..=nl])?;
+            self.0.drain(..=nl);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.1.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tempdir::TempDir;
+    use std::fs::OpenOptions;
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    fn append(path: &Path, text: &str) {
+        OpenOptions::new().append(true).open(path).unwrap().write_all(text.as_bytes()).unwrap();
+    }
+
+    //every read has something to return or a restart, a read that waits would hang the test
+    fn read(follow: &mut Follow) -> String {
+        let mut buf = [0; 64];
+        let read = follow.read(&mut buf).unwrap();
+        String::from_utf8_lossy(&buf[..read]).into_owned()
+    }
+
+    #[test]
+    fn truncation_and_rotation() {
+        let dir = TempDir::new("follow");
+        let path = dir.join("app.log");
+        fs::write(&path, "one\n").unwrap();
+
+        let mut follow = Follow::open(&path).unwrap();
+        follow.poll = Duration::from_millis(1);
+        assert_eq!("one\n", read(&mut follow));
+        append(&path, "two\n");
+        assert_eq!("two\n", read(&mut follow));
+
+        //copytruncate: the same file, starting over
+        fs::write(&path, "3\n").unwrap();
+        assert_eq!(("", Some(Restart::Truncated)), (read(&mut follow).as_str(), follow.take_restart()));
+        assert_eq!("3\n", read(&mut follow));
+
+        //rotation: the old file is read to its end before the new one
+        fs::rename(&path, dir.join("app.log.1")).unwrap();
+        fs::write(&path, "new\n").unwrap();
+        append(&dir.join("app.log.1"), "4\n");
+        assert_eq!("4\n", read(&mut follow));
+        assert_eq!(("", Some(Restart::Replaced)), (read(&mut follow).as_str(), follow.take_restart()));
+        assert_eq!("new\n", read(&mut follow));
+    }
+    //somewhere to print to that the test can look at while follow_file writes to it
+    #[derive(Clone, Default)]
+    struct Printed(Arc<Mutex<Vec<u8>>>);
+
+    impl Printed {
+        //waits a while for `expected` to be all that's been printed, and gives what there is
+        fn wait_for(&self, expected: &str) -> String {
+            let start = Instant::now();
+            loop {
+                let printed = String::from_utf8(self.0.lock().unwrap().clone()).unwrap();
+                if printed == expected || start.elapsed() > Duration::from_secs(5) {
+                    return printed;
+                }
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+    }
+
+    impl Write for Printed {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn appended_and_truncated() {
+        let dir = TempDir::new("follow-file");
+        let path = dir.join("app.log");
+        fs::write(&path, "one hit\n").unwrap();
+
+        let args: Vec<String> = ["minigrep", "-s", "hit"].iter().map(|s| s.to_string()).collect();
+        let config = Config::from_args(&args).unwrap();
+        let re = Regex::build("hit", false).unwrap();
+        let printed = Printed::default();
+        let (sender, receiver) = mpsc::channel();
+        let (follow_path, out) = (path.clone(), printed.clone());
+        //never returns, it goes away with the test process
+        thread::spawn(move || {
+            let mut printer = Printer::new(false, false, false, 0, 0, false);
+            follow_file(follow_path, &re, &config, &mut printer, out, &sender)
+        });
+
+        //a line added at the end is found once, not again on the next look at the file
+        assert_eq!("one hit\n", printed.wait_for("one hit\n"));
+        append(&path, "two hit\n");
+        assert_eq!("one hit\ntwo hit\n", printed.wait_for("one hit\ntwo hit\n"));
+        thread::sleep(POLL * 2);
+        assert_eq!("one hit\ntwo hit\n", printed.wait_for("one hit\ntwo hit\n"));
+
+        //half a line, then copytruncate and the writer finishing it in the new start of the file
+        append(&path, "par");
+        thread::sleep(POLL * 4);
+        fs::write(&path, "tial hit\n").unwrap();
+        //the search that ended at the restart, without the half line
+        let stats = receiver.recv_timeout(Duration::from_secs(5)).unwrap().1.unwrap();
+        assert_eq!((2, 16), (stats.matched_lines, stats.bytes_searched));
+        let expected = "one hit\ntwo hit\npartial hit\n";
+        assert_eq!(expected, printed.wait_for(expected));
+    }
+}
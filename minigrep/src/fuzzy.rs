@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+
+use crate::casefold;
+use crate::matcher::Matcher;
+
+//--fuzzy K: a line matches when some part of it is at most K edits (a char
+//inserted, deleted or changed) away from the query, the levenshtein
+//distance. filling in the whole table of distances costs a column of
+//minimums per char of text. myers' bit-parallel algorithm keeps that column
+//as bits saying whether each entry is one more, one less or the same as
+//the one above it, and gets the next column with a handful of word
+//operations per 64 chars of query
+//
+//  G. Myers, A fast bit-vector algorithm for approximate string matching
+//  based on dynamic programming, JACM 46(3), 1999
+
+/// A match of a Fuzzy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FuzzyMatch {
+    pub start: usize,
+    pub end: usize,
+    pub distance: usize, //how many edits the text is from the query
+    pub pattern: usize,  //which of the queries it was
+}
+
+/// Finds text within a number of edits of one of its queries. The queries
+/// are plain text, like the ones of a Literal.
+#[derive(Debug, Clone)]
+pub struct Fuzzy {
+    queries: Vec<Query>,
+    max_edits: usize,
+    ignore_case: bool,
+}
+
+//one query, with the bits the algorithm needs for it: for every char of the
+//query, a bit for each position the char is at. the query backwards too,
+//to find where a match starts once its end is known
+#[derive(Debug, Clone)]
+struct Query {
+    len: usize, //in chars
+    forward: Positions,
+    backward: Positions,
+}
+
+#[derive(Debug, Clone)]
+struct Positions {
+    words: usize,
+    ascii: Vec<u64>, //`words` words for every ascii char
+    other: HashMap<char, Vec<u64>>,
+    none: Vec<u64>, //for the chars that aren't in the query
+}
+
+//how far past the first place a query is within the budget a scan goes
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scan {
+    First,    //is there a match at all
+    Leftmost, //the leftmost one, carried on as long as it gets closer to the query
+    Best,     //the closest one on the line
+}
+
+impl Fuzzy {
+    pub fn new(query: &str, max_edits: usize) -> Fuzzy {
+        Fuzzy::many(&[query], max_edits, false)
+    }
+
+    /// Matches the text of any of `queries`, ignoring case like -i if
+    /// `ignore_case`. When two of them match the same way, the first one
+    /// in the list wins.
+    pub fn many<S: AsRef<str>>(queries: &[S], max_edits: usize, ignore_case: bool) -> Fuzzy {
+        let queries = queries
+            .iter()
+            .map(|query| {
+                let chars: Vec<char> = query.as_ref().chars().map(|c| fold(c, ignore_case)).collect();
+                Query {
+                    len: chars.len(),
+                    forward: Positions::new(chars.iter().copied()),
+                    backward: Positions::new(chars.iter().rev().copied()),
+                }
+            })
+            .collect();
+        Fuzzy {
+            queries,
+            max_edits,
+            ignore_case,
+        }
+    }
+
+    pub fn max_edits(&self) -> usize {
+        self.max_edits
+    }
+
+    /// The match in `text` at or after `start` that is the fewest edits
+    /// away from its query, and the leftmost of those.
+    pub fn best_at(&self, text: &str, start: usize) -> Option<FuzzyMatch> {
+        self.find(text, start, Scan::Best, |m| (m.distance, m.start, m.end))
+    }
+
+    /// Like find_at, with how far the match is from its query.
+    pub fn leftmost_at(&self, text: &str, start: usize) -> Option<FuzzyMatch> {
+        self.find(text, start, Scan::Leftmost, |m| (m.start, m.distance, m.end))
+    }
+
+    //the match of every query, and then the one that's smallest by `rank`
+    fn find<K: Ord>(
+        &self,
+        text: &str,
+        start: usize,
+        scan: Scan,
+        rank: impl Fn(&FuzzyMatch) -> K,
+    ) -> Option<FuzzyMatch> {
+        if start > text.len() {
+            return None;
+        }
+        let mut found: Option<FuzzyMatch> = None;
+        for (pattern, query) in self.queries.iter().enumerate() {
+            let Some((end, distance)) = query.scan(text, start, self.max_edits, self.ignore_case, scan) else {
+                continue;
+            };
+            let match_start = query.start_of(text, start, end, distance, self.ignore_case);
+            let (end, distance) = query.end_of(text, match_start, end, distance, self.ignore_case);
+            let m = FuzzyMatch {
+                start: match_start,
+                end,
+                distance,
+                pattern,
+            };
+            if found.is_none_or(|found| rank(&m) < rank(&found)) {
+                found = Some(m);
+            }
+        }
+        found
+    }
+}
+
+impl Matcher for Fuzzy {
+    fn find_at(&self, text: &str, start: usize) -> Option<(usize, usize)> {
+        self.leftmost_at(text, start).map(|m| (m.start, m.end))
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        let any = |query: &Query| {
+            query
+                .scan(text, 0, self.max_edits, self.ignore_case, Scan::First)
+                .is_some()
+        };
+        self.queries.iter().any(any)
+    }
+}
+
+fn fold(c: char, ignore_case: bool) -> char {
+    match ignore_case {
+        true => casefold::fold(c),
+        false => c,
+    }
+}
+
+impl Positions {
+    fn new(chars: impl Iterator<Item = char> + Clone) -> Positions {
+        let words = chars.clone().count().div_ceil(64);
+        let mut positions = Positions {
+            words,
+            ascii: vec![0; 128 * words],
+            other: HashMap::new(),
+            none: vec![0; words],
+        };
+        for (i, c) in chars.enumerate() {
+            let bits = match c.is_ascii() {
+                true => &mut positions.ascii[c as usize * words..(c as usize + 1) * words],
+                false => positions.other.entry(c).or_insert_with(|| vec![0; words]),
+            };
+            bits[i / 64] |= 1 << (i % 64);
+        }
+        positions
+    }
+
+    fn of(&self, c: char) -> &[u64] {
+        match c.is_ascii() {
+            true => &self.ascii[c as usize * self.words..(c as usize + 1) * self.words],
+            false => self.other.get(&c).unwrap_or(&self.none),
+        }
+    }
+}
+
+//a column of the table as bits: a bit of vp for every entry that's one more
+//than the one above it, of vn for one less. the others are the same
+struct Column {
+    vp: Vec<u64>,
+    vn: Vec<u64>,
+    bottom: u64, //the bit of the query's last char in the last word
+    last: usize, //the bottom entry, the distance of the whole query
+}
+
+impl Column {
+    //the column before any text: the query deleted one char at a time
+    fn new(len: usize) -> Column {
+        Column {
+            vp: vec![!0; len.div_ceil(64)],
+            vn: vec![0; len.div_ceil(64)],
+            bottom: 1 << (len.saturating_sub(1) % 64),
+            last: len,
+        }
+    }
+
+    //the next column, for a char that's at the positions `eq` of the query.
+    //`top` is how the entry above the query changes from one column to the
+    //next: 0 when a match can start anywhere, 1 when it has to start where
+    //the columns did. straight from the paper's advance_block, one word at
+    //a time with what the last word's bottom did carried into the next
+    fn advance(&mut self, eq: &[u64], top: i8) {
+        let mut carry = top;
+        for (i, &eq) in eq.iter().enumerate() {
+            let (vp, vn) = (self.vp[i], self.vn[i]);
+            let xv = eq | vn;
+            let eq = if carry < 0 { eq | 1 } else { eq };
+            let xh = ((eq & vp).wrapping_add(vp) ^ vp) | eq;
+            let mut hp = vn | !(xh | vp);
+            let mut hn = vp & xh;
+
+            let bottom = if i + 1 == self.vp.len() { self.bottom } else { 1 << 63 };
+            let out = if hp & bottom != 0 {
+                1
+            } else if hn & bottom != 0 {
+                -1
+            } else {
+                0
+            };
+
+            hp <<= 1;
+            hn <<= 1;
+            if carry < 0 {
+                hn |= 1;
+            } else if carry > 0 {
+                hp |= 1;
+            }
+            self.vp[i] = hn | !(xv | hp);
+            self.vn[i] = hp & xv;
+            carry = out;
+        }
+        self.last = self.last.wrapping_add_signed(carry as isize);
+    }
+}
+
+impl Query {
+    //where a match of the query ends in text[start..], and its distance
+    fn scan(
+        &self,
+        text: &str,
+        start: usize,
+        max_edits: usize,
+        ignore_case: bool,
+        scan: Scan,
+    ) -> Option<(usize, usize)> {
+        let mut column = Column::new(self.len);
+        //before any text at all is the query with every char deleted
+        let mut found = (column.last <= max_edits).then_some((start, column.last));
+        for (i, c) in text[start..].char_indices() {
+            match found {
+                Some((_, 0)) => break, //can't get any better than that
+                Some(_) if scan == Scan::First => break,
+                _ => {}
+            }
+            column.advance(self.forward.of(fold(c, ignore_case)), 0);
+            let end = start + i + c.len_utf8();
+            match found {
+                Some((_, distance)) if column.last < distance => found = Some((end, column.last)),
+                //the leftmost match is as good as it gets once it stops getting better
+                Some(_) if scan == Scan::Leftmost => break,
+                None if column.last <= max_edits => found = Some((end, column.last)),
+                _ => {}
+            }
+        }
+        found
+    }
+
+    //where the shortest match that ends at `end` and is `distance` away
+    //starts: the query backwards, run over the text backwards from `end`,
+    //both anchored there. the scan already said there is one after `start`
+    fn start_of(&self, text: &str, start: usize, end: usize, distance: usize, ignore_case: bool) -> usize {
+        let mut column = Column::new(self.len);
+        if column.last <= distance {
+            return end;
+        }
+        for (i, c) in text[start..end].char_indices().rev() {
+            column.advance(self.backward.of(fold(c, ignore_case)), 1);
+            if column.last <= distance {
+                return start + i;
+            }
+        }
+        start
+    }
+
+    //the longest match that starts at `start` and is no further from the
+    //query than the one that ends at `end`. the scan stops at the first end
+    //that's close enough, which for recieve is recie: two edits like the
+    //whole word, with reciev in between being three
+    fn end_of(&self, text: &str, start: usize, end: usize, distance: usize, ignore_case: bool) -> (usize, usize) {
+        let mut column = Column::new(self.len);
+        let mut found = (end, distance);
+        //more chars than that are more edits than the whole query
+        for (i, c) in text[start..].char_indices().take(self.len + distance) {
+            column.advance(self.forward.of(fold(c, ignore_case)), 1);
+            let at = start + i + c.len_utf8();
+            if at >= end && column.last <= found.1 {
+                found = (at, column.last);
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    //the textbook table, to check the bits against
+    fn levenshtein(a: &[char], b: &[char]) -> usize {
+        let mut row: Vec<usize> = (0..=b.len()).collect();
+        for (i, &ca) in a.iter().enumerate() {
+            let mut diagonal = row[0];
+            row[0] = i + 1;
+            for (j, &cb) in b.iter().enumerate() {
+                let next = (diagonal + (ca != cb) as usize).min(row[j] + 1).min(row[j + 1] + 1);
+                diagonal = row[j + 1];
+                row[j + 1] = next;
+            }
+        }
+        row[b.len()]
+    }
+
+    //the same table with a free start in `text`: the distance of the closest substring
+    fn closest(query: &[char], text: &[char]) -> usize {
+        let mut column: Vec<usize> = (0..=query.len()).collect();
+        let mut closest = query.len();
+        for &c in text {
+            let mut diagonal = column[0];
+            for (i, &q) in query.iter().enumerate() {
+                let next = (diagonal + (q != c) as usize).min(column[i + 1] + 1).min(column[i] + 1);
+                diagonal = column[i + 1];
+                column[i + 1] = next;
+            }
+            closest = closest.min(column[query.len()]);
+        }
+        closest
+    }
+
+    #[test]
+    fn edits() {
+        let fuzzy = Fuzzy::new("receive", 2);
+        let best = |text| fuzzy.best_at(text, 0).map(|m| (&text[m.start..m.end], m.distance));
+        assert_eq!(Some(("receive", 0)), best("we receive it"));
+        assert_eq!(Some(("recieve", 2)), best("we recieve it")); //a swap is two edits
+        assert_eq!(Some(("receve", 1)), best("we receve it"));
+        assert_eq!(None, best("nothing like it"));
+
+        //the closest match wins over the leftmost one, which find_at goes for
+        let text = "recieve or receive";
+        assert_eq!(Some((11, 18)), fuzzy.best_at(text, 0).map(|m| (m.start, m.end)));
+        assert_eq!(Some((0, 7)), fuzzy.find_at(text, 0));
+        assert_eq!(vec![(0, 7), (11, 18)], fuzzy.find_iter(text).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn long_queries_and_case() {
+        //more than a word of bits, with the difference in the second word
+        let query = "the quick brown fox jumps over the lazy dog, then does it again and again";
+        let typo = query.replace("again and", "agian and");
+        let text = format!("> {typo} <");
+        let m = Fuzzy::new(query, 3).best_at(&text, 0).unwrap();
+        assert_eq!((2, text.len() - 2, 2), (m.start, m.end, m.distance));
+        let query: Vec<char> = query.chars().collect();
+        let typo: Vec<char> = typo.chars().collect();
+        assert_eq!(levenshtein(&query, &typo), m.distance);
+
+        let any_case = Fuzzy::many(&["straße"], 1, true);
+        assert_eq!(
+            Some((4, 10, 1)),
+            any_case.best_at("die STRASE", 0).map(|m| (m.start, m.end, m.distance))
+        );
+        assert!(!Fuzzy::new("straße", 1).is_match("die STRASE"));
+    }
+
+    #[test]
+    fn same_as_the_table() {
+        //the closest substring of every text the slow way, for queries that
+        //take one word of bits and ones that take two
+        let mut seed = 7u32;
+        let mut random = |n: u32| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) % n
+        };
+        for round in 0..200 {
+            let len = if round % 2 == 0 {
+                1 + random(10)
+            } else {
+                60 + random(20)
+            };
+            let query: Vec<char> = (0..len).map(|_| (b'a' + random(3) as u8) as char).collect();
+            let text: Vec<char> = (0..random(120)).map(|_| (b'a' + random(3) as u8) as char).collect();
+            let closest = closest(&query, &text);
+
+            let (query, text): (String, String) = (query.iter().collect(), text.iter().collect());
+            let m = Fuzzy::new(&query, len as usize).best_at(&text, 0).unwrap();
+            let query: Vec<char> = query.chars().collect();
+            let found: Vec<char> = text[m.start..m.end].chars().collect();
+            assert_eq!(
+                (closest, closest),
+                (m.distance, levenshtein(&query, &found)),
+                "{query:?} in {text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn queries() {
+        let fuzzy = Fuzzy::many(&["colour", "gray"], 1, false);
+        let m = fuzzy.best_at("a grey colour", 0).unwrap();
+        assert_eq!((7, 13, 0, 0), (m.start, m.end, m.distance, m.pattern));
+        let m = fuzzy.leftmost_at("a grey colour", 0).unwrap();
+        assert_eq!((2, 6, 1, 1), (m.start, m.end, m.distance, m.pattern));
+    }
+}
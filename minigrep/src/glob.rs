@@ -0,0 +1,168 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::regex::Regex;
+
+//shell-style globs for --include/--exclude and ignore files. instead of
+//writing a second matcher we translate the glob into a regex and let
+//the regex engine do the work:
+//  *      anything except '/'
+//  **     anything at all, so **/ means "in any directory"
+//  ?      one char except '/'
+//  [abc]  [!abc] [a-z]  character classes
+//  \x     a literal x
+#[derive(Debug, Clone)]
+pub struct Glob {
+    re: Regex,
+    has_slash: bool,
+}
+
+/// A glob that doesn't make sense, like one with a class range
+/// backwards. `glob` is the glob as it was given.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobError {
+    pub glob: String,
+    pub msg: &'static str,
+}
+
+impl fmt::Display for GlobError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid glob '{}': {}", self.glob, self.msg)
+    }
+}
+
+impl Error for GlobError {}
+
+impl Glob {
+    pub fn build(pattern: &str) -> Result<Glob, GlobError> {
+        //the regex is ours, so what's wrong with it is what's wrong with the glob,
+        //but the position in it isn't worth anything to whoever wrote the glob
+        let re = Regex::build(&to_regex(pattern), false).map_err(|err| GlobError {
+            glob: pattern.to_string(),
+            msg: err.msg,
+        })?;
+        Ok(Glob {
+            re,
+            has_slash: pattern.contains('/'),
+        })
+    }
+
+    pub fn is_match(&self, path: &str) -> bool {
+        self.re.is_match(path)
+    }
+
+    //globs without a '/' (like *.rs) are about the file name only, so
+    //"src/main.rs" is matched by comparing "main.rs"
+    pub fn matches_path(&self, path: &str) -> bool {
+        if self.has_slash {
+            self.is_match(path)
+        } else {
+            self.is_match(file_name(path))
+        }
+    }
+}
+
+pub(crate) fn file_name(path: &str) -> &str {
+    match path.rfind('/') {
+        Some(i) => &path[i + 1..],
+        None => path,
+    }
+}
+
+fn to_regex(glob: &str) -> String {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut re = String::from("^");
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let at_start = i == 0 || chars[i - 1] == '/';
+                if at_start && chars.get(i + 2) == Some(&'/') {
+                    //"**/" matches zero or more whole directories
+                    re.push_str("(?:.*/)?");
+                    i += 3;
+                } else {
+                    re.push_str(".*");
+                    i += 2;
+                }
+                continue;
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '[' => match chars[i + 1..].iter().skip(1).position(|&c| c == ']') {
+                //skip(1) bc a ']' right after '[' is part of the class
+                Some(len) => {
+                    let end = i + 2 + len;
+                    re.push('[');
+                    let mut j = i + 1;
+                    if chars[j] == '!' || chars[j] == '^' {
+                        re.push('^');
+                        j += 1;
+                    }
+                    for &c in &chars[j..end] {
+                        if c == '\\' || c == '[' {
+                            re.push('\\');
+                        }
+                        re.push(c);
+                    }
+                    re.push(']');
+                    i = end;
+                }
+                None => re.push_str("\\["), //no closing bracket, so it's just a '['
+            },
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                push_literal(&mut re, chars[i]);
+            }
+            _ => push_literal(&mut re, c),
+        }
+        i += 1;
+    }
+
+    re.push('$');
+    re
+}
+
+fn push_literal(re: &mut String, c: char) {
+    if "\\.+*?()|[]{}^$".contains(c) {
+        re.push('\\');
+    }
+    re.push(c);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stars_and_classes() {
+        let glob = Glob::build("*.rs").unwrap();
+        assert!(glob.is_match("main.rs"));
+        assert!(!glob.is_match("src/main.rs"));
+        assert!(glob.matches_path("src/main.rs"));
+        assert!(!glob.matches_path("src/main.rsx"));
+
+        let glob = Glob::build("log[0-9].t?t").unwrap();
+        assert!(glob.is_match("log7.txt"));
+        assert!(!glob.is_match("logs.txt"));
+        assert!(Glob::build("[!a]*").unwrap().is_match("b.txt"));
+        assert_eq!(
+            "invalid glob '[z-a].txt': invalid range in character class",
+            Glob::build("[z-a].txt").unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn double_star() {
+        let glob = Glob::build("**/target/**").unwrap();
+        assert!(glob.is_match("target/debug/minigrep"));
+        assert!(glob.is_match("a/b/target/x"));
+        assert!(!glob.is_match("a/targets/x"));
+
+        let glob = Glob::build("src/**/*.rs").unwrap();
+        assert!(glob.is_match("src/lib.rs"));
+        assert!(glob.is_match("src/a/b/lib.rs"));
+    }
+}
@@ -0,0 +1,523 @@
+use std::io::{self, BufRead, Read};
+use std::mem;
+
+//a gzip decoder (rfc 1952) around a hand written inflate (rfc 1951), so
+//-z doesn't need the flate2 crate. it's a Read, so the decompressed text
+//streams into the same line reader as everything else and a huge .gz is
+//never decompressed into memory all at once.
+//
+//deflate data is a series of blocks, each either stored (raw bytes), or
+//compressed with a fixed or a dynamic (sent in the block header) pair of
+//huffman codes: one for literal bytes and match lengths, one for match
+//distances. a match copies up to 258 bytes from as far as 32K back in the
+//output, so the last 32K of output is kept around as a window
+
+const WINDOW: usize = 32 * 1024;
+
+//the magic bytes a gzip file starts with
+pub const MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+fn corrupt(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("corrupt gzip data: {msg}"))
+}
+
+//deflate packs its fields starting at the lowest bit of each byte
+struct Bits<R: BufRead> {
+    inner: R,
+    bits: u64,
+    count: u32,
+}
+
+impl<R: BufRead> Bits<R> {
+    fn refill(&mut self) -> io::Result<()> {
+        while self.count <= 56 {
+            let buf = self.inner.fill_buf()?;
+            if buf.is_empty() {
+                break;
+            }
+            let take = buf.len().min(((64 - self.count) / 8) as usize);
+            for &byte in &buf[..take] {
+                self.bits |= (byte as u64) << self.count;
+                self.count += 8;
+            }
+            self.inner.consume(take);
+        }
+        Ok(())
+    }
+
+    fn take(&mut self, n: u32) -> io::Result<u32> {
+        if self.count < n {
+            self.refill()?;
+            if self.count < n {
+                return Err(corrupt("unexpected end of file"));
+            }
+        }
+        let value = (self.bits & ((1u64 << n) - 1)) as u32;
+        self.drop_bits(n);
+        Ok(value)
+    }
+
+    fn drop_bits(&mut self, n: u32) {
+        self.bits >>= n;
+        self.count -= n;
+    }
+
+    fn byte(&mut self) -> io::Result<u8> {
+        self.take(8).map(|byte| byte as u8)
+    }
+
+    //stored blocks and the gzip trailer start on a byte boundary
+    fn align(&mut self) {
+        self.drop_bits(self.count % 8);
+    }
+
+    fn at_end(&mut self) -> io::Result<bool> {
+        Ok(self.count == 0 && self.inner.fill_buf()?.is_empty())
+    }
+}
+
+const FAST_BITS: u32 = 9;
+
+//a canonical huffman code. codes up to FAST_BITS long are decoded with one
+//table lookup, longer ones (rare) one bit at a time like zlib's puff.c
+struct Huffman {
+    counts: [u16; 16], //how many codes there are of each length
+    symbols: Vec<u16>, //sorted by code length, then by symbol
+    fast: Vec<(u16, u8)>, //indexed by the next FAST_BITS bits: (symbol, length), length 0 if the code is longer
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> io::Result<Huffman> {
+        let mut counts = [0u16; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        //more codes of a length than there's room for means it's garbage.
+        //fewer is allowed, a distance code with just one symbol is like that
+        let mut left: i32 = 1;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(corrupt("bad huffman code lengths"));
+            }
+        }
+
+        let mut symbols: Vec<u16> = (0..lengths.len() as u16).filter(|&s| lengths[s as usize] != 0).collect();
+        symbols.sort_by_key(|&s| lengths[s as usize]);
+
+        //the first code of every length, then every symbol's code in order
+        let mut next = [0u32; 16];
+        for len in 1..16 {
+            next[len] = (next[len - 1] + counts[len - 1] as u32) << 1;
+        }
+        let mut fast = vec![(0, 0); 1 << FAST_BITS];
+        for &symbol in &symbols {
+            let len = lengths[symbol as usize] as u32;
+            let code = next[len as usize];
+            next[len as usize] += 1;
+            if len <= FAST_BITS {
+                //codes are sent starting at their highest bit, so the table is indexed backwards
+                let reversed = code.reverse_bits() >> (32 - len);
+                for fill in 0..1 << (FAST_BITS - len) {
+                    fast[(reversed | fill << len) as usize] = (symbol, len as u8);
+                }
+            }
+        }
+
+        Ok(Huffman { counts, symbols, fast })
+    }
+
+    fn decode<R: BufRead>(&self, bits: &mut Bits<R>) -> io::Result<u16> {
+        if bits.count < 15 {
+            bits.refill()?;
+        }
+        let (symbol, len) = self.fast[(bits.bits & ((1 << FAST_BITS) - 1)) as usize];
+        if len > 0 && len as u32 <= bits.count {
+            bits.drop_bits(len as u32);
+            return Ok(symbol);
+        }
+
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..16 {
+            code |= bits.take(1)? as i32;
+            let count = self.counts[len] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(corrupt("bad huffman code"))
+    }
+}
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097,
+    6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+//the order the code length code lengths come in, most used first
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xedb8_8320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+fn crc32(crc: u32, bytes: &[u8]) -> u32 {
+    let mut c = !crc;
+    for &byte in bytes {
+        c = CRC_TABLE[((c ^ byte as u32) & 0xff) as usize] ^ (c >> 8);
+    }
+    !c
+}
+
+enum State {
+    Header,
+    Block,         //the next block header
+    Stored(usize), //bytes left in a stored block
+    Codes(Huffman, Huffman), //in a compressed block: literal/length and distance codes
+    Trailer,
+    Done,
+}
+
+/// Decompresses a gzip stream as it's read. Files with several gzip
+/// members one after the other (what `cat a.gz b.gz` makes) come out as
+/// one stream, like gunzip does it.
+pub struct GzDecoder<R: BufRead> {
+    bits: Bits<R>,
+    state: State,
+    last_block: bool,
+    members: usize,
+    out: Vec<u8>, //the window plus the output that hasn't been read yet
+    pos: usize,   //how much of out has been read
+    crc: u32,
+    crc_pos: usize, //how much of out is in crc already
+    size: u32,      //output size of this member, mod 2^32 like the trailer has it
+}
+
+impl<R: BufRead> GzDecoder<R> {
+    pub fn new(inner: R) -> GzDecoder<R> {
+        GzDecoder {
+            bits: Bits { inner, bits: 0, count: 0 },
+            state: State::Header,
+            last_block: false,
+            members: 0,
+            out: Vec::with_capacity(3 * WINDOW),
+            pos: 0,
+            crc: 0,
+            crc_pos: 0,
+            size: 0,
+        }
+    }
+
+    fn update_crc(&mut self) {
+        self.crc = crc32(self.crc, &self.out[self.crc_pos..]);
+        self.size = self.size.wrapping_add((self.out.len() - self.crc_pos) as u32);
+        self.crc_pos = self.out.len();
+    }
+
+    //decodes about a window's worth of output, or less if the stream ends
+    fn fill(&mut self) -> io::Result<()> {
+        if self.pos > 2 * WINDOW {
+            //everything but the last window has been read and can't be referred back to anymore
+            self.update_crc();
+            let drop = self.pos - WINDOW;
+            self.out.drain(..drop);
+            self.pos -= drop;
+            self.crc_pos -= drop;
+        }
+
+        let target = self.out.len() + WINDOW;
+        while self.out.len() < target {
+            self.state = match mem::replace(&mut self.state, State::Done) {
+                State::Header => match self.header()? {
+                    true => State::Block,
+                    false => State::Done,
+                },
+                State::Block if self.last_block => State::Trailer,
+                State::Block => self.block_header()?,
+                State::Stored(left) => {
+                    let n = left.min(target - self.out.len());
+                    for _ in 0..n {
+                        let byte = self.bits.byte()?;
+                        self.out.push(byte);
+                    }
+                    if n == left {
+                        State::Block
+                    } else {
+                        State::Stored(left - n)
+                    }
+                }
+                State::Codes(lit, dist) => match self.codes(&lit, &dist, target)? {
+                    true => State::Block,
+                    false => State::Codes(lit, dist),
+                },
+                State::Trailer => {
+                    self.update_crc();
+                    self.bits.align();
+                    let crc = u32::from_le_bytes([self.bits.byte()?, self.bits.byte()?, self.bits.byte()?, self.bits.byte()?]);
+                    let size = u32::from_le_bytes([self.bits.byte()?, self.bits.byte()?, self.bits.byte()?, self.bits.byte()?]);
+                    if crc != self.crc || size != self.size {
+                        return Err(corrupt("checksum mismatch"));
+                    }
+                    if self.bits.at_end()? {
+                        State::Done
+                    } else {
+                        State::Header
+                    }
+                }
+                State::Done => break,
+            };
+        }
+        Ok(())
+    }
+
+    //false if there's no further member, just some trailing garbage (which gunzip ignores too)
+    fn header(&mut self) -> io::Result<bool> {
+        if [self.bits.byte()?, self.bits.byte()?] != MAGIC {
+            return match self.members {
+                0 => Err(corrupt("not a gzip file")),
+                _ => Ok(false),
+            };
+        }
+        if self.bits.byte()? != 8 {
+            return Err(corrupt("unknown compression method"));
+        }
+        let flags = self.bits.byte()?;
+        for _ in 0..6 {
+            self.bits.byte()?; //modification time, extra flags and os
+        }
+        if flags & 0x04 != 0 {
+            let len = self.bits.byte()? as usize | (self.bits.byte()? as usize) << 8;
+            for _ in 0..len {
+                self.bits.byte()?;
+            }
+        }
+        //the original file name and a comment, zero terminated
+        for flag in [0x08, 0x10] {
+            if flags & flag != 0 {
+                while self.bits.byte()? != 0 {}
+            }
+        }
+        if flags & 0x02 != 0 {
+            self.bits.take(16)?; //a crc of the header
+        }
+
+        self.members += 1;
+        self.last_block = false;
+        self.crc = 0;
+        self.size = 0;
+        Ok(true)
+    }
+
+    fn block_header(&mut self) -> io::Result<State> {
+        self.last_block = self.bits.take(1)? == 1;
+        match self.bits.take(2)? {
+            0 => {
+                self.bits.align();
+                let len = self.bits.take(16)?;
+                if self.bits.take(16)? != !len & 0xffff {
+                    return Err(corrupt("bad stored block length"));
+                }
+                Ok(State::Stored(len as usize))
+            }
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                Ok(State::Codes(Huffman::new(&lengths)?, Huffman::new(&[5; 30])?))
+            }
+            2 => self.dynamic_codes(),
+            _ => Err(corrupt("bad block type")),
+        }
+    }
+
+    fn dynamic_codes(&mut self) -> io::Result<State> {
+        let literals = self.bits.take(5)? as usize + 257;
+        let distances = self.bits.take(5)? as usize + 1;
+        let code_lengths = self.bits.take(4)? as usize + 4;
+
+        //the code lengths of the two codes are themselves huffman coded
+        let mut lengths = [0u8; 19];
+        for &i in &CODE_LENGTH_ORDER[..code_lengths] {
+            lengths[i] = self.bits.take(3)? as u8;
+        }
+        let code = Huffman::new(&lengths)?;
+
+        let mut lengths = vec![0u8; literals + distances];
+        let mut i = 0;
+        while i < lengths.len() {
+            let (len, repeat) = match code.decode(&mut self.bits)? {
+                symbol @ 0..=15 => (symbol as u8, 1),
+                16 if i > 0 => (lengths[i - 1], 3 + self.bits.take(2)? as usize),
+                17 => (0, 3 + self.bits.take(3)? as usize),
+                18 => (0, 11 + self.bits.take(7)? as usize),
+                _ => return Err(corrupt("bad code lengths")),
+            };
+            if i + repeat > lengths.len() {
+                return Err(corrupt("too many code lengths"));
+            }
+            lengths[i..i + repeat].fill(len);
+            i += repeat;
+        }
+        if lengths[256] == 0 {
+            return Err(corrupt("no end of block code"));
+        }
+
+        let (lit, dist) = lengths.split_at(literals);
+        Ok(State::Codes(Huffman::new(lit)?, Huffman::new(dist)?))
+    }
+
+    //decodes until the block ends (true) or out has reached target (false)
+    fn codes(&mut self, lit: &Huffman, dist: &Huffman, target: usize) -> io::Result<bool> {
+        while self.out.len() < target {
+            let symbol = lit.decode(&mut self.bits)? as usize;
+            if symbol < 256 {
+                self.out.push(symbol as u8);
+                continue;
+            }
+            if symbol == 256 {
+                return Ok(true);
+            }
+
+            let i = symbol - 257;
+            if i >= LENGTH_BASE.len() {
+                return Err(corrupt("bad length code"));
+            }
+            let len = LENGTH_BASE[i] as usize + self.bits.take(LENGTH_EXTRA[i] as u32)? as usize;
+            let d = dist.decode(&mut self.bits)? as usize;
+            if d >= DIST_BASE.len() {
+                return Err(corrupt("bad distance code"));
+            }
+            let distance = DIST_BASE[d] as usize + self.bits.take(DIST_EXTRA[d] as u32)? as usize;
+            if distance > self.out.len() {
+                return Err(corrupt("distance too far back"));
+            }
+
+            //byte by byte, since the copy can overlap what it's copying (distance 1 repeats a byte)
+            let from = self.out.len() - distance;
+            for k in 0..len {
+                let byte = self.out[from + k];
+                self.out.push(byte);
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl<R: BufRead> Read for GzDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.out.len() {
+            if let State::Done = self.state {
+                return Ok(0);
+            }
+            self.fill()?;
+        }
+        let n = buf.len().min(self.out.len() - self.pos);
+        buf[..n].copy_from_slice(&self.out[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// The name of the compression format `head` (the start of a file) is in, if any.
+pub fn format(head: &[u8]) -> Option<&'static str> {
+    if head.starts_with(&MAGIC) {
+        Some("gzip")
+    } else if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Some("zstd")
+    } else if head.starts_with(b"BZh") {
+        Some("bzip2")
+    } else if head.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0]) {
+        Some("xz")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn gunzip(data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        GzDecoder::new(data).read_to_end(&mut out)?;
+        Ok(out)
+    }
+
+    //python's gzip.compress(b"Rust:\nsafe, fast, productive.\nPick three.\n", mtime=0), one fixed huffman block
+    const FIXED: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x0b, 0x2a, 0x2d, 0x2e, 0xb1, 0xe2, 0x2a,
+        0x4e, 0x4c, 0x4b, 0xd5, 0x51, 0x48, 0x4b, 0x2c, 0x2e, 0xd1, 0x51, 0x28, 0x28, 0xca, 0x4f, 0x29, 0x4d,
+        0x2e, 0xc9, 0x2c, 0x4b, 0xd5, 0xe3, 0x0a, 0xc8, 0x4c, 0xce, 0x56, 0x28, 0xc9, 0x28, 0x4a, 0x05, 0xb2,
+        0x01, 0xaf, 0x76, 0xe3, 0x31, 0x2a, 0x00, 0x00, 0x00
+    ];
+
+    #[test]
+    fn fixed_and_dynamic_blocks() {
+        assert_eq!(&b"Rust:\nsafe, fast, productive.\nPick three.\n"[..], gunzip(FIXED).unwrap());
+
+        //gzip -9 poem.txt: a dynamic huffman block, and a file name in the header
+        let poem = [
+            0x1f, 0x8b, 0x08, 0x08, 0x00, 0x00, 0x00, 0x00, 0x02, 0xff, 0x70, 0x6f, 0x65, 0x6d, 0x2e, 0x74, 0x78,
+            0x74, 0x00, 0x2d, 0x8e, 0xb1, 0x0e, 0x83, 0x30, 0x0c, 0x44, 0xf7, 0x7c, 0xc5, 0x31, 0xb1, 0xd0, 0xfe,
+            0x02, 0xea, 0xd6, 0xee, 0x48, 0x9d, 0x9d, 0xc6, 0x40, 0x44, 0x88, 0x2b, 0x13, 0x8a, 0xf2, 0xf7, 0x4d,
+            0x68, 0x37, 0xeb, 0xee, 0xe9, 0xf9, 0x1e, 0xed, 0x8a, 0x28, 0x56, 0x5c, 0x6e, 0xf0, 0x9c, 0x05, 0xa4,
+            0x8c, 0x2c, 0x7b, 0x6f, 0x6e, 0xbf, 0xe3, 0x5f, 0x76, 0x48, 0x22, 0xbd, 0x19, 0x66, 0x8e, 0x48, 0x33,
+            0x2b, 0xb7, 0x1b, 0x08, 0x6f, 0xf2, 0x0a, 0x19, 0xb1, 0x6f, 0xb8, 0xc0, 0x49, 0x6c, 0x13, 0x12, 0x87,
+            0xd0, 0x54, 0x2e, 0xb7, 0x0e, 0x96, 0xa2, 0xdf, 0xe6, 0x52, 0x77, 0xa7, 0x6a, 0x89, 0x72, 0x5c, 0x8d,
+            0xb9, 0xcb, 0x01, 0xa7, 0x4c, 0x9a, 0x8b, 0x14, 0x96, 0xb1, 0xc9, 0xca, 0xe7, 0x82, 0xb3, 0x7a, 0xef,
+            0x36, 0xf8, 0x57, 0x87, 0xe0, 0x17, 0x2e, 0x3f, 0x46, 0x95, 0xc9, 0x0c, 0x72, 0x8a, 0xab, 0x45, 0x11,
+            0x69, 0xe5, 0x3a, 0xa2, 0x10, 0x1f, 0x0e, 0x12, 0x27, 0x38, 0xca, 0x15, 0xa1, 0x08, 0x72, 0xab, 0x57,
+            0x5f, 0x22, 0x2b, 0x53, 0xf3, 0x05, 0x5e, 0x90, 0x2b, 0xba, 0xdc, 0x00, 0x00, 0x00
+        ];
+        assert_eq!(include_bytes!("../poem.txt")[..], gunzip(&poem).unwrap());
+    }
+
+    #[test]
+    fn several_members_and_stored_blocks() {
+        //gzip.compress(b"abc\n", compresslevel=0, mtime=0), a stored block
+        let stored = [
+            0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x03, 0x01, 0x04, 0x00, 0xfb, 0xff, 0x61, 0x62,
+            0x63, 0x0a, 0x4e, 0x81, 0x88, 0x47, 0x04, 0x00, 0x00, 0x00
+        ];
+        let both = [FIXED, &stored, b"\0\0"].concat(); //the zeros are trailing garbage and get ignored
+        assert_eq!(&b"Rust:\nsafe, fast, productive.\nPick three.\nabc\n"[..], gunzip(&both).unwrap());
+    }
+
+    #[test]
+    fn corrupt_data() {
+        let mut bad = FIXED.to_vec();
+        bad[20] ^= 0x40;
+        assert!(gunzip(&bad).is_err());
+        assert!(gunzip(&FIXED[..30]).is_err());
+        assert!(gunzip(b"plain text").is_err());
+        assert_eq!(Some("gzip"), format(FIXED));
+        assert_eq!(Some("zstd"), format(&[0x28, 0xb5, 0x2f, 0xfd, 0]));
+        assert_eq!(None, format(b"Rust:"));
+    }
+}
@@ -0,0 +1,699 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::casefold;
+use crate::encoding::{self, Encoding};
+use crate::regex::Required;
+use crate::walk::{WalkError, Walker};
+use crate::{Config, OutputMode, Regex};
+
+//minigrep index build DIR writes down, for every file under DIR, which
+//trigrams (runs of three bytes) are in it. a file can only match "foobar"
+//if it has foo, oob, oba and bar in it, so with --index the files that are
+//missing one of them don't have to be read at all. the ones that are left
+//still get searched the normal way, the index only ever rules files out.
+//
+//files that changed since the index was built (a different modification
+//time or size) or that aren't in it are searched no matter what, so a
+//stale index is slower but never wrong. building again only reads those.
+//
+//the index is one file in DIR:
+//  MAGIC
+//  u32 number of files, then per file: u32 path length, the path (relative
+//      to DIR, with /), u64 + u32 modification time, u64 size
+//  u32 number of trigrams, then per trigram: u32 trigram, u32 number of
+//      files, u64 where its list starts
+//  the lists: the ids (positions in the file table) of the files that have
+//      the trigram, sorted and stored as varint differences
+//all numbers little endian
+
+pub const FILE_NAME: &str = ".minigrep-index";
+pub const TEMP_FILE_NAME: &str = ".minigrep-index.tmp"; //what a build writes before renaming it
+const MAGIC: &[u8] = b"minigrep index 1\n";
+
+//what says whether the index still knows what's in a file
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Stamp {
+    modified: (u64, u32), //seconds and nanoseconds since the epoch
+    size: u64,
+}
+
+impl Stamp {
+    fn of(meta: &fs::Metadata) -> Stamp {
+        let modified = meta.modified().ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok()).unwrap_or_default();
+        Stamp {
+            modified: (modified.as_secs(), modified.subsec_nanos()),
+            size: meta.len(),
+        }
+    }
+}
+
+/// How `minigrep index build` went.
+#[derive(Debug, Default)]
+pub struct IndexStats {
+    pub files: usize,            //in the index now
+    pub read: usize,             //new or changed since the last build, the rest came from the old index
+    pub removed: usize,          //in the old index but gone now
+    pub skipped: Vec<WalkError>, //files and directories that couldn't be read, and so aren't in it
+}
+
+//an index read from disk. only the file table gets read up front, the
+//trigram table can be big and a search only needs a few entries of it, so
+//those get looked up with a binary search right in the file
+struct Index {
+    files: Vec<(String, Stamp)>,
+    file: File,
+    trigrams: u64, //how many entries the table has
+    table: u64,    //where it starts
+    lists: u64,    //and where the lists after it start
+    end: u64,      //where the last list ends
+}
+
+//the size of an entry in the trigram table
+const ENTRY: u64 = 16;
+
+impl Index {
+    fn open(dir: &Path) -> io::Result<Index> {
+        let mut reader = BufReader::new(File::open(dir.join(FILE_NAME))?);
+        let mut magic = [0; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(invalid("not a minigrep index"));
+        }
+
+        let count = read_u32(&mut reader)?;
+        let mut files = Vec::new();
+        for _ in 0..count {
+            let len = read_u32(&mut reader)? as usize;
+            let mut path = Vec::new();
+            (&mut reader).take(len as u64).read_to_end(&mut path)?;
+            if path.len() != len {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let path = String::from_utf8(path).map_err(|_| invalid("path isn't utf-8"))?;
+            let modified = (read_u64(&mut reader)?, read_u32(&mut reader)?);
+            let size = read_u64(&mut reader)?;
+            files.push((path, Stamp { modified, size }));
+        }
+
+        let trigrams = read_u32(&mut reader)? as u64;
+        let table = reader.stream_position()?;
+        let file = reader.into_inner();
+        let end = file.metadata()?.len();
+        let lists = table + trigrams * ENTRY;
+        if lists > end {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let mut index = Index {
+            files,
+            file,
+            trigrams,
+            table,
+            lists,
+            end,
+        };
+        //an index that got cut off is missing the end of its last list, so
+        //that one gets read now instead of whenever a search needs it
+        if trigrams > 0 {
+            let (last, _, _) = index.entry(trigrams - 1)?;
+            index.list(last)?;
+        }
+        Ok(index)
+    }
+
+    //entry i of the table: (trigram, length of its list, where the list starts)
+    fn entry(&mut self, i: u64) -> io::Result<(u32, u32, u64)> {
+        self.file.seek(SeekFrom::Start(self.table + i * ENTRY))?;
+        let mut entry = [0; ENTRY as usize];
+        self.file.read_exact(&mut entry)?;
+        let field = |at: usize| u32::from_le_bytes(entry[at..at + 4].try_into().unwrap());
+        let at = u64::from_le_bytes(entry[8..16].try_into().unwrap());
+        Ok((field(0), field(4), at))
+    }
+
+    //the ids of the files that have `trigram`, in order
+    fn list(&mut self, trigram: u32) -> io::Result<Vec<u32>> {
+        let (mut low, mut high) = (0, self.trigrams);
+        while low < high {
+            let mid = low + (high - low) / 2;
+            let (found, len, at) = self.entry(mid)?;
+            if found < trigram {
+                low = mid + 1;
+            } else if found > trigram {
+                high = mid;
+            } else {
+                //a list ends where the next one starts
+                let end = match mid + 1 < self.trigrams {
+                    true => self.entry(mid + 1)?.2,
+                    false => self.end - self.lists,
+                };
+                let mut bytes = vec![0; end.checked_sub(at).ok_or_else(|| invalid("bad list"))? as usize];
+                self.file.seek(SeekFrom::Start(self.lists + at))?;
+                self.file.read_exact(&mut bytes)?;
+                let mut ids = Vec::with_capacity(len as usize);
+                decode(&bytes, len, self.files.len(), |id| ids.push(id))?;
+                return Ok(ids);
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    //every list there is, for a rebuild: `found` gets every (trigram, id)
+    fn all_lists(&mut self, mut found: impl FnMut(u32, u32)) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start(self.table))?;
+        let mut reader = BufReader::new(&mut self.file);
+        let mut table = Vec::with_capacity(self.trigrams as usize);
+        for _ in 0..self.trigrams {
+            let trigram = read_u32(&mut reader)?;
+            let len = read_u32(&mut reader)?;
+            table.push((trigram, len, read_u64(&mut reader)?));
+        }
+        let mut lists = Vec::new();
+        reader.read_to_end(&mut lists)?;
+
+        for (i, &(trigram, len, at)) in table.iter().enumerate() {
+            let end = table.get(i + 1).map_or(lists.len() as u64, |next| next.2);
+            let bytes = lists.get(at as usize..end as usize).ok_or_else(|| invalid("bad list"))?;
+            decode(bytes, len, self.files.len(), |id| found(trigram, id))?;
+        }
+        Ok(())
+    }
+}
+
+//a list is `len` varints, each the difference to the id before it. every
+//id has to be one of the `files`
+fn decode(bytes: &[u8], len: u32, files: usize, mut id: impl FnMut(u32)) -> io::Result<()> {
+    let mut pos = 0;
+    let mut last = 0u32;
+    for _ in 0..len {
+        let mut delta = 0u32;
+        for shift in (0..35).step_by(7) {
+            let byte = *bytes.get(pos).ok_or_else(|| invalid("list too short"))?;
+            pos += 1;
+            delta |= ((byte & 0x7f) as u32).checked_shl(shift).unwrap_or(0);
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        last = last.checked_add(delta).filter(|&next| (next as usize) < files).ok_or_else(|| invalid("bad file id"))?;
+        id(last);
+    }
+    Ok(())
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("broken index: {msg}"))
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+//7 bits at a time, low bits first, the high bit set on all but the last byte
+fn write_varint(out: &mut Vec<u8>, mut value: u32) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+//three bytes as one number, with ascii lowercased so the same index
+//works for case insensitive searches
+fn trigram(bytes: &[u8]) -> u32 {
+    (bytes[0].to_ascii_lowercase() as u32) << 16
+        | (bytes[1].to_ascii_lowercase() as u32) << 8
+        | bytes[2].to_ascii_lowercase() as u32
+}
+
+//finds the distinct trigrams of a file by sorting them all and dropping
+//the repeats, which needs memory for what's in the file and not for every
+//trigram there could be. the vec is kept for the next file
+#[derive(Default)]
+struct Trigrams {
+    found: Vec<u32>,
+}
+
+impl Trigrams {
+    fn of(&mut self, bytes: &[u8]) -> &[u32] {
+        self.found.clear();
+        self.found.extend(bytes.windows(3).map(trigram));
+        self.found.sort_unstable();
+        self.found.dedup();
+        &self.found
+    }
+}
+
+//the path of a file under the index's directory, the way it's stored
+fn key(relative: &Path) -> String {
+    let parts: Vec<_> = relative.components().map(|part| part.as_os_str().to_string_lossy()).collect();
+    parts.join("/")
+}
+
+/// Builds the index of `dir` (what `minigrep index build DIR` does), or
+/// brings it up to date: only files that are new or that changed are
+/// read, the rest is taken from the index that's already there. The
+/// files are the ones a search of `dir` would look at, so ignore files
+/// count. Files that can't be read are left out, and are in the stats.
+pub fn build_index(dir: &Path) -> Result<IndexStats, Box<dyn Error>> {
+    let mut old = match Index::open(dir) {
+        Ok(index) => Some(index),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+        //a broken index gets replaced, which is what it needs
+        Err(err) if matches!(err.kind(), io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof) => None,
+        Err(err) => return Err(err.into()),
+    };
+    let old_files: HashMap<String, (u32, Stamp)> = match &old {
+        Some(old) => old.files.iter().enumerate().map(|(id, (path, stamp))| (path.clone(), (id as u32, *stamp))).collect(),
+        None => HashMap::new(),
+    };
+
+    let mut stats = IndexStats::default();
+    let mut files = Vec::new();
+    let mut pairs = Pairs::default();
+    let mut kept = vec![None; old.as_ref().map_or(0, |old| old.files.len())]; //new ids of the files that didn't change
+    let mut trigrams = Trigrams::default();
+    let walker = Walker::build(&[], &[])?;
+    for file in walker.files(&[dir.to_string_lossy().into_owned()]) {
+        let result = file.and_then(|path| match fs::metadata(&path) {
+            Ok(meta) => Ok((path, meta)),
+            Err(err) => Err(WalkError { path, err }),
+        });
+        let (path, meta) = match result {
+            Ok(file) => file,
+            Err(err) => {
+                stats.skipped.push(err);
+                continue;
+            }
+        };
+        let key = key(path.strip_prefix(dir).unwrap_or(&path));
+        let stamp = Stamp::of(&meta);
+        let id = files.len() as u32;
+
+        match old_files.get(&key) {
+            Some(&(old_id, old_stamp)) if old_stamp == stamp => {
+                kept[old_id as usize] = Some(id);
+            }
+            _ => {
+                //a file with a byte order mark gets searched as utf-8, so that's what gets indexed
+                let bytes = match fs::read(&path).and_then(encoding::decode_bom) {
+                    Ok(bytes) => bytes,
+                    Err(err) => {
+                        stats.skipped.push(WalkError { path, err });
+                        continue;
+                    }
+                };
+                for &trigram in trigrams.of(&bytes) {
+                    pairs.push(trigram, id);
+                }
+                stats.read += 1;
+            }
+        }
+        files.push((key, stamp));
+    }
+
+    //the files that didn't change keep the trigrams they had, under their new ids
+    if let Some(old) = &mut old {
+        old.all_lists(|trigram, id| {
+            if let Some(id) = kept[id as usize] {
+                pairs.push(trigram, id);
+            }
+        })?;
+        let now: HashSet<&str> = files.iter().map(|(key, _)| key.as_str()).collect();
+        stats.removed = old_files.keys().filter(|key| !now.contains(key.as_str())).count();
+    }
+
+    stats.files = files.len();
+    write(dir, &files, pairs)?;
+    Ok(stats)
+}
+
+//every (trigram, file id) there is, in one vec instead of a list per
+//trigram: a big tree has millions of distinct trigrams and pushing onto
+//that many little vecs in a map is most of what building would cost.
+//a pair is one number with the trigram in the high half, so sorting them
+//puts them in order of trigram and then of id
+#[derive(Default)]
+struct Pairs(Vec<u64>);
+
+impl Pairs {
+    fn push(&mut self, trigram: u32, id: u32) {
+        self.0.push((trigram as u64) << 32 | id as u64);
+    }
+
+    //the pairs of a trigram end up next to each other, with their ids in order
+    fn sorted(mut self) -> Vec<u64> {
+        self.0.sort_unstable();
+        self.0
+    }
+}
+
+//into a temp file first, so a search never sees half an index
+fn write(dir: &Path, files: &[(String, Stamp)], pairs: Pairs) -> io::Result<()> {
+    let temp = dir.join(TEMP_FILE_NAME);
+    let mut out = BufWriter::new(File::create(&temp)?);
+    out.write_all(MAGIC)?;
+    out.write_all(&(files.len() as u32).to_le_bytes())?;
+    for (path, stamp) in files {
+        out.write_all(&(path.len() as u32).to_le_bytes())?;
+        out.write_all(path.as_bytes())?;
+        out.write_all(&stamp.modified.0.to_le_bytes())?;
+        out.write_all(&stamp.modified.1.to_le_bytes())?;
+        out.write_all(&stamp.size.to_le_bytes())?;
+    }
+
+    //the table says where every list starts, so the lists get encoded first
+    let pairs = pairs.sorted();
+    let mut table = Vec::new();
+    let mut encoded = Vec::new();
+    for list in pairs.chunk_by(|a, b| a >> 32 == b >> 32) {
+        table.push(((list[0] >> 32) as u32, list.len() as u32, encoded.len() as u64));
+        let mut last = 0;
+        for &pair in list {
+            let id = pair as u32;
+            write_varint(&mut encoded, id - last);
+            last = id;
+        }
+    }
+    out.write_all(&(table.len() as u32).to_le_bytes())?;
+    for (trigram, len, at) in table {
+        out.write_all(&trigram.to_le_bytes())?;
+        out.write_all(&len.to_le_bytes())?;
+        out.write_all(&at.to_le_bytes())?;
+    }
+    out.write_all(&encoded)?;
+    out.flush()?;
+    drop(out);
+    fs::rename(temp, dir.join(FILE_NAME))
+}
+
+//the trigrams a file needs to have to possibly match, from what the regex
+//says every match has in it
+#[derive(Debug, PartialEq)]
+enum Query {
+    Anything,
+    Trigrams(Vec<u32>),
+    All(Vec<Query>),
+    Any(Vec<Query>),
+}
+
+impl Query {
+    fn new(required: &Required) -> Query {
+        match required {
+            Required::Anything => Query::Anything,
+            Required::Text(text, ignore_case) => Query::text(text, *ignore_case),
+            Required::All(all) => {
+                let mut all: Vec<Query> = all.iter().map(Query::new).filter(|query| *query != Query::Anything).collect();
+                match all.len() {
+                    0 => Query::Anything,
+                    1 => all.pop().unwrap(),
+                    _ => Query::All(all),
+                }
+            }
+            Required::Any(any) => {
+                let any: Vec<Query> = any.iter().map(Query::new).collect();
+                match any.contains(&Query::Anything) {
+                    true => Query::Anything,
+                    false => Query::Any(any),
+                }
+            }
+        }
+    }
+
+    fn text(text: &str, ignore_case: bool) -> Query {
+        //the index only folds ascii. with -i a char that has a non-ascii
+        //version (k and the kelvin sign) could be that in the file, so the
+        //text is cut up there. and the search turns bytes that aren't utf-8
+        //into U+FFFD, which the index never saw
+        let cut = |c: char| c == '\u{fffd}' || (ignore_case && casefold::variants(c).any(|variant| !variant.is_ascii()));
+        let trigrams: Vec<u32> = text.split(cut).flat_map(|piece| piece.as_bytes().windows(3).map(trigram)).collect();
+        match trigrams.is_empty() {
+            true => Query::Anything,
+            false => Query::Trigrams(trigrams),
+        }
+    }
+
+    //the ids of the files that can match, None for all of them
+    fn files(&self, index: &mut Index) -> io::Result<Option<Vec<u32>>> {
+        Ok(match self {
+            Query::Anything => None,
+            Query::Trigrams(trigrams) => {
+                let mut ids = index.list(trigrams[0])?;
+                for &trigram in &trigrams[1..] {
+                    if ids.is_empty() {
+                        break;
+                    }
+                    ids = intersect(&ids, &index.list(trigram)?);
+                }
+                Some(ids)
+            }
+            Query::All(all) => {
+                let mut ids: Option<Vec<u32>> = None;
+                for query in all {
+                    if let Some(more) = query.files(index)? {
+                        ids = Some(match ids {
+                            Some(ids) => intersect(&ids, &more),
+                            None => more,
+                        });
+                    }
+                }
+                ids
+            }
+            Query::Any(any) => {
+                let mut ids = Vec::new();
+                for query in any {
+                    match query.files(index)? {
+                        Some(more) => ids.extend(more),
+                        None => return Ok(None),
+                    }
+                }
+                ids.sort_unstable();
+                ids.dedup();
+                Some(ids)
+            }
+        })
+    }
+}
+
+fn intersect(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut both = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] < b[j] {
+            i += 1;
+        } else if a[i] > b[j] {
+            j += 1;
+        } else {
+            both.push(a[i]);
+            i += 1;
+            j += 1;
+        }
+    }
+    both
+}
+
+//the path made absolute without touching the file system, so it can be
+//compared with where an index is
+fn absolute(cwd: &Path, path: &Path) -> PathBuf {
+    let mut absolute = PathBuf::new();
+    for part in cwd.join(path).components() {
+        match part {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                absolute.pop();
+            }
+            part => absolute.push(part),
+        }
+    }
+    absolute
+}
+
+//an index with what it says about the current search
+struct Narrowed {
+    dir: PathBuf,
+    ids: HashMap<String, (u32, Stamp)>,
+    matching: Option<Vec<u32>>, //None if the index can't rule any file out
+}
+
+/// --index: leaves out the files the index says can't have a match. Every
+/// path searched needs an index in it or in a directory above it. With
+/// -v, -c or -L files without a match show up in the output too, so then
+/// nothing is left out.
+pub fn narrow(
+    files: Vec<Result<PathBuf, WalkError>>,
+    re: &Regex,
+    config: &Config,
+) -> Result<Vec<Result<PathBuf, WalkError>>, Box<dyn Error>> {
+    if config.invert_match || matches!(config.mode, OutputMode::Count | OutputMode::FilesWithoutMatch) {
+        return Ok(files);
+    }
+    //a field's value can be escaped or quoted, and text in another encoding
+    //has other bytes than its utf-8, so the text needn't be in the file
+    if config.field.is_some() || config.encoding != Encoding::Utf8 {
+        return Ok(files);
+    }
+    let query = Query::new(re.required());
+
+    let cwd = env::current_dir()?;
+    let mut indexes: Vec<Narrowed> = Vec::new();
+    for path in config.paths.iter().filter(|path| *path != "-") {
+        let absolute = absolute(&cwd, Path::new(path));
+        let dir = absolute
+            .ancestors()
+            .find(|dir| dir.join(FILE_NAME).is_file())
+            .ok_or_else(|| format!("no index for {path}, make one with 'minigrep index build DIR'"))?;
+        if indexes.iter().any(|index| index.dir == dir) {
+            continue;
+        }
+        let mut index = Index::open(dir).map_err(|err| format!("{}: {err}", dir.join(FILE_NAME).display()))?;
+        let matching = query.files(&mut index)?;
+        let ids = index.files.drain(..).enumerate().map(|(id, (key, stamp))| (key, (id as u32, stamp))).collect();
+        indexes.push(Narrowed {
+            dir: dir.to_path_buf(),
+            ids,
+            matching,
+        });
+    }
+
+    let can_match = |path: &Path| {
+        let absolute = absolute(&cwd, path);
+        //the closest index, when a directory with one has one of its own further down
+        let Some(index) = indexes.iter().filter(|index| absolute.starts_with(&index.dir)).max_by_key(|index| index.dir.as_os_str().len()) else {
+            return true;
+        };
+        let Some(matching) = &index.matching else {
+            return true;
+        };
+        let key = key(absolute.strip_prefix(&index.dir).unwrap_or(&absolute));
+        match (index.ids.get(&key), fs::metadata(path)) {
+            //what the index knows about it is still true
+            (Some(&(id, stamp)), Ok(meta)) if Stamp::of(&meta) == stamp => matching.binary_search(&id).is_ok(),
+            //new, changed or gone, the search will find out
+            _ => true,
+        }
+    };
+    Ok(files.into_iter().filter(|file| file.as_ref().map_or(true, |path| can_match(path))).collect())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tempdir::TempDir;
+
+    fn config(args: &[&str]) -> Config {
+        let args: Vec<String> = ["minigrep"].iter().chain(args).map(|s| s.to_string()).collect();
+        Config::from_args(&args).unwrap()
+    }
+
+    //the names of the files left after narrowing a search of dirs
+    fn narrow_dirs(dirs: &[&Path], args: &[&str]) -> Result<Vec<String>, Box<dyn Error>> {
+        let dirs: Vec<String> = dirs.iter().map(|dir| dir.to_string_lossy().into_owned()).collect();
+        let args: Vec<&str> = args.iter().copied().chain(dirs.iter().map(String::as_str)).collect();
+        let config = config(&args);
+        let re = Regex::build_many(&crate::patterns(&config), config.ignore_case).unwrap();
+        let files = Walker::build(&[], &[]).unwrap().files(&config.paths);
+        let files = narrow(files, &re, &config)?;
+        Ok(files.into_iter().map(|file| file.unwrap().file_name().unwrap().to_string_lossy().into_owned()).collect())
+    }
+
+    fn narrowed(dir: &Path, args: &[&str]) -> Vec<String> {
+        narrow_dirs(&[dir], args).unwrap()
+    }
+
+    #[test]
+    fn narrowing_and_updating() {
+        let dir = TempDir::new("index");
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("a.txt"), "the Kelvin scale\n").unwrap();
+        fs::write(dir.join("src/b.rs"), "fn search(query: &str)\n").unwrap();
+        fs::write(dir.join("src/c.rs"), "fn main() { run() }\n").unwrap();
+        //what a build that got killed leaves behind isn't a file to index
+        fs::write(dir.join(TEMP_FILE_NAME), "search").unwrap();
+
+        let stats = build_index(&dir).unwrap();
+        assert_eq!((3, 3, 0, 0), (stats.files, stats.read, stats.removed, stats.skipped.len()));
+
+        assert_eq!(vec!["b.rs"], narrowed(&dir, &["search"]));
+        assert_eq!(vec!["b.rs", "c.rs"], narrowed(&dir, &[r"fn \w+\("]));
+        assert_eq!(vec!["a.txt", "c.rs"], narrowed(&dir, &["-e", "kelvin", "-e", "main", "-i"]));
+        //with -i the k could be a kelvin sign, so only "elvin" is required
+        assert_eq!(vec!["a.txt"], narrowed(&dir, &["-i", "\u{212a}ELVIN"]));
+        assert_eq!(vec!["a.txt", "b.rs", "c.rs"], narrowed(&dir, &["-c", "search"]));
+        assert!(narrowed(&dir, &["-F", "nowhere"]).is_empty());
+
+        //a changed file is searched until the index is built again, which only reads that one
+        fs::write(dir.join("src/c.rs"), "fn main() { search() }\n").unwrap();
+        fs::remove_file(dir.join("a.txt")).unwrap();
+        assert_eq!(vec!["b.rs", "c.rs"], narrowed(&dir, &["search"]));
+        let stats = build_index(&dir).unwrap();
+        assert_eq!((2, 1, 1), (stats.files, stats.read, stats.removed));
+        assert_eq!(vec!["b.rs", "c.rs"], narrowed(&dir, &["search"]));
+        assert_eq!(vec!["b.rs"], narrowed(&dir.join("src"), &["query"]));
+    }
+
+    #[test]
+    fn broken_index() {
+        let dir = TempDir::new("index-broken");
+        fs::write(dir.join("a.txt"), "the Kelvin scale\n").unwrap();
+        fs::write(dir.join("b.txt"), "fn search(query: &str)\n").unwrap();
+        build_index(&dir).unwrap();
+        let index = fs::read(dir.join(FILE_NAME)).unwrap();
+
+        //not an index at all, or one that got cut off: a search says so, a build starts over
+        for broken in [&b"something else entirely\n"[..], &index[..MAGIC.len() + 6], &index[..index.len() - 2]] {
+            fs::write(dir.join(FILE_NAME), broken).unwrap();
+            let err = narrow_dirs(&[&dir], &["search"]).unwrap_err().to_string();
+            assert!(err.starts_with(&format!("{}: ", dir.join(FILE_NAME).display())), "{err}");
+
+            let stats = build_index(&dir).unwrap();
+            assert_eq!((2, 2, 0), (stats.files, stats.read, stats.removed));
+            assert_eq!(vec!["b.txt"], narrowed(&dir, &["search"]));
+        }
+    }
+
+    #[test]
+    fn decoding_lists() {
+        let decoded = |bytes: &[u8], len: u32, files: usize| {
+            let mut ids = Vec::new();
+            decode(bytes, len, files, |id| ids.push(id)).map(|_| ids).map_err(|err| err.to_string())
+        };
+        //differences to the id before, and one that takes two bytes
+        assert_eq!(Ok(vec![0, 2, 3, 200]), decoded(&[0, 2, 1, 0xc5, 0x01], 4, 201));
+        assert_eq!(Err("broken index: bad file id".to_string()), decoded(&[0, 2, 1, 0xc5, 0x01], 4, 200));
+        assert_eq!(Err("broken index: bad file id".to_string()), decoded(&[1, 0xff, 0xff, 0xff, 0xff, 0x0f], 2, 10));
+        assert_eq!(Err("broken index: list too short".to_string()), decoded(&[0, 0x80], 2, 10));
+    }
+
+    #[test]
+    fn which_index() {
+        let dir = TempDir::new("index-nested");
+        let sub = dir.join("sub");
+        fs::create_dir_all(&sub).unwrap();
+        //a search has to say where to look for an index
+        assert!(narrow_dirs(&[&dir], &["zzz"]).unwrap_err().to_string().starts_with("no index for "));
+
+        fs::write(dir.join("a.txt"), "zzz\n").unwrap();
+        fs::write(sub.join("old.txt"), "nothing\n").unwrap();
+        build_index(&dir).unwrap();
+        //a file the outer index doesn't know about, but the one in sub does
+        fs::write(sub.join("new.txt"), "nothing either\n").unwrap();
+        build_index(&sub).unwrap();
+
+        assert_eq!(vec!["a.txt", "new.txt"], narrowed(&dir, &["zzz"]));
+        //with both, every file goes by the index closest to it
+        assert_eq!(vec!["a.txt"], narrow_dirs(&[&dir, &sub], &["zzz"]).unwrap());
+    }
+}
@@ -0,0 +1,177 @@
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str;
+
+use crate::literal;
+use crate::{BinaryFiles, Regex, Stats};
+
+//--in-place: the new contents go into a temp file in the same directory,
+//which then gets renamed over the original. a rename within a directory
+//is atomic, so anyone reading the file sees either all of the old version
+//or all of the new one, and a crash halfway leaves the original alone.
+//the backup is a hard link to the original, made before the rename, so
+//it costs nothing and has the old contents once the rename is done.
+//a symlink is followed first, like sed --follow-symlinks, or the rename
+//would put a plain file where the link was and leave its target alone
+
+/// Replaces every match in the file at `path`. Lines without a match, and
+/// lines that aren't valid utf-8, are copied over byte for byte, line
+/// endings included. A file without any match isn't touched at all, and
+/// neither is a binary one unless `binary` is BinaryFiles::Text. If `path`
+/// is a symlink it's the file it points to that gets rewritten and backed up.
+pub fn rewrite(
+    path: &Path,
+    re: &Regex,
+    replacement: &str,
+    backup_suffix: &str,
+    binary: BinaryFiles,
+) -> io::Result<Stats> {
+    if path == Path::new("-") {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "can only rewrite files in place"));
+    }
+    let path = &fs::canonicalize(path)?;
+    let file_name = match path.file_name() {
+        Some(name) => name,
+        None => return Err(io::Error::new(io::ErrorKind::InvalidInput, "can only rewrite files in place")),
+    };
+    let mut temp_name = OsString::from(".");
+    temp_name.push(file_name);
+    temp_name.push(format!(".minigrep-{}", process::id()));
+    let temp = path.with_file_name(temp_name);
+
+    let stats = match write_replaced(path, &temp, re, replacement, binary == BinaryFiles::Text) {
+        Ok(stats) if stats.matched_lines > 0 => stats,
+        other => {
+            //nothing to change, or it went wrong halfway
+            let _ = fs::remove_file(&temp);
+            return other;
+        }
+    };
+
+    if !backup_suffix.is_empty() {
+        let mut backup = path.as_os_str().to_owned();
+        backup.push(backup_suffix);
+        let backup = PathBuf::from(backup);
+        let _ = fs::remove_file(&backup); //an old backup gets replaced
+        //not every file system has hard links
+        if fs::hard_link(path, &backup).is_err() {
+            fs::copy(path, &backup)?;
+        }
+    }
+
+    fs::rename(&temp, path).inspect_err(|_| {
+        let _ = fs::remove_file(&temp);
+    })?;
+    Ok(stats)
+}
+
+fn write_replaced(path: &Path, temp: &Path, re: &Regex, replacement: &str, text: bool) -> io::Result<Stats> {
+    let input = File::open(path)?;
+    let permissions = input.metadata()?.permissions();
+    let mut reader = BufReader::new(input);
+    let output = File::options().write(true).create_new(true).open(temp)?;
+    output.set_permissions(permissions)?;
+    let mut writer = BufWriter::new(output);
+
+    let mut stats = Stats {
+        searches: 1,
+        ..Stats::default()
+    };
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        let read = reader.read_until(b'\n', &mut buf)?;
+        if read == 0 {
+            break;
+        }
+        stats.bytes_searched += read;
+        //only NUL bytes count here, a stray latin-1 byte in a text file is
+        //still worth rewriting around
+        if !text && literal::memchr(0, &buf).is_some() {
+            return Ok(Stats {
+                searches: 1,
+                ..Stats::default()
+            });
+        }
+
+        let ending = if buf.ends_with(b"\r\n") {
+            2
+        } else {
+            buf.ends_with(b"\n") as usize
+        };
+        let (line, ending) = buf.split_at(buf.len() - ending);
+        match str::from_utf8(line) {
+            Ok(text) if re.is_match(text) => {
+                let (replaced, spans) = re.replace_all(text, replacement);
+                stats.matched_lines += 1;
+                stats.matches += spans.len();
+                writer.write_all(replaced.as_bytes())?;
+                writer.write_all(ending)?;
+            }
+            _ => writer.write_all(&buf)?,
+        }
+    }
+
+    //make sure it's all on disk before it replaces the original
+    writer.into_inner().map_err(|err| err.into_error())?.sync_all()?;
+    if stats.matched_lines > 0 {
+        stats.searches_with_match = 1;
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tempdir::TempDir;
+
+    #[test]
+    fn rewrites_with_backup() {
+        let dir = TempDir::new("inplace");
+        let path = dir.join("notes.txt");
+        let original: &[u8] = b"color: red\r\nno match\ncaf\xe9 color\ncolor";
+        fs::write(&path, original).unwrap();
+
+        let re = Regex::build("colou?r", false).unwrap();
+        let stats = rewrite(&path, &re, "colour", ".orig", BinaryFiles::Binary).unwrap();
+        assert_eq!((2, 2), (stats.matched_lines, stats.matches));
+        //the crlf stays, and the line that isn't utf-8 is left exactly as it was
+        assert_eq!(&b"colour: red\r\nno match\ncaf\xe9 color\ncolour"[..], fs::read(&path).unwrap());
+        assert_eq!(original, fs::read(dir.join("notes.txt.orig")).unwrap());
+
+        //nothing to replace: no rewrite, no backup, no temp file left behind
+        let re = Regex::build("blue", false).unwrap();
+        assert_eq!(0, rewrite(&path, &re, "green", ".nope", BinaryFiles::Binary).unwrap().matched_lines);
+        assert!(!dir.join("notes.txt.nope").exists());
+        assert_eq!(2, fs::read_dir(&dir).unwrap().count());
+
+        //binary files are left alone, unless -a says they're text
+        let binary = dir.join("blue.bin");
+        fs::write(&binary, b"blue\x00").unwrap();
+        assert_eq!(0, rewrite(&binary, &re, "green", "", BinaryFiles::Binary).unwrap().matched_lines);
+        assert_eq!(1, rewrite(&binary, &re, "green", "", BinaryFiles::Text).unwrap().matched_lines);
+        assert_eq!(&b"green\x00"[..], fs::read(&binary).unwrap());
+
+        assert!(rewrite(Path::new("-"), &re, "green", "", BinaryFiles::Binary).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn follows_symlinks() {
+        let dir = TempDir::new("inplace-symlink");
+        let (real, link) = (dir.join("real.txt"), dir.join("link.txt"));
+        fs::write(&real, "old\n").unwrap();
+        std::os::unix::fs::symlink("real.txt", &link).unwrap();
+
+        let re = Regex::build("old", false).unwrap();
+        assert_eq!(1, rewrite(&link, &re, "new", ".bak", BinaryFiles::Binary).unwrap().matched_lines);
+        //the link is still a link, to a file that's been rewritten and backed up next to it
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!("new\n", fs::read_to_string(&real).unwrap());
+        assert_eq!("old\n", fs::read_to_string(dir.join("real.txt.bak")).unwrap());
+        assert!(!dir.join("link.txt.bak").exists());
+    }
+}
@@ -0,0 +1,150 @@
+use std::io::{self, Write};
+use std::time::Duration;
+
+use crate::printer::Labels;
+use crate::{Match, Stats};
+
+//--json output, one object per line, in the same shape as ripgrep's so
+//tools that already read `rg --json` can read ours:
+//  {"type":"begin","data":{"path":{"text":"poem.txt"}}}
+//  {"type":"match","data":{"path":{..},"lines":{"text":"..\n"},"line_number":9,
+//      "absolute_offset":201,"submatches":[{"match":{"text":"bog"},"start":15,"end":18}]}}
+//  {"type":"context", same as match but without submatches}
+//  with --fuzzy a match also has "distance":1 after the submatches, and with
+//  --show-pattern "pattern":"bog" after that
+//  {"type":"end","data":{"path":{..},"binary_offset":null,"stats":{..}}}
+//  {"type":"summary","data":{"elapsed_total":{..},"stats":{..}}}
+//there are no serde crates in here, so the json gets written by hand
+
+pub fn begin(out: &mut impl Write, path: &str) -> io::Result<()> {
+    writeln!(out, r#"{{"type":"begin","data":{{"path":{{"text":{}}}}}}}"#, string(path))
+}
+
+//kind is "match" or "context"
+pub fn line(
+    out: &mut impl Write,
+    kind: &str,
+    path: &str,
+    m: &Match,
+    spans: &[(usize, usize)],
+    labels: Labels,
+) -> io::Result<()> {
+    //a -U match can end with the newline after the line
+    let text = format!("{}\n", m.line);
+    let submatches: Vec<String> = spans
+        .iter()
+        .map(|&(start, end)| {
+            format!(
+                r#"{{"match":{{"text":{}}},"start":{start},"end":{end}}}"#,
+                string(&text[start..end.min(text.len())])
+            )
+        })
+        .collect();
+    let distance = match labels.distance {
+        Some(distance) => format!(r#","distance":{distance}"#),
+        None => String::new(),
+    };
+    let pattern = match labels.pattern {
+        Some(pattern) => format!(r#","pattern":{}"#, string(pattern)),
+        None => String::new(),
+    };
+
+    writeln!(
+        out,
+        r#"{{"type":"{kind}","data":{{"path":{{"text":{}}},"lines":{{"text":{}}},"line_number":{},"absolute_offset":{},"submatches":[{}]{distance}{pattern}}}}}"#,
+        string(path),
+        string(&text),
+        m.line_number,
+        m.byte_offset,
+        submatches.join(",")
+    )
+}
+
+pub fn end(out: &mut impl Write, path: &str, stats: &Stats) -> io::Result<()> {
+    writeln!(
+        out,
+        r#"{{"type":"end","data":{{"path":{{"text":{}}},"binary_offset":null,"stats":{}}}}}"#,
+        string(path),
+        stats_object(stats)
+    )
+}
+
+pub fn summary(out: &mut impl Write, stats: &Stats, elapsed_total: Duration) -> io::Result<()> {
+    writeln!(
+        out,
+        r#"{{"type":"summary","data":{{"elapsed_total":{},"stats":{}}}}}"#,
+        duration(elapsed_total),
+        stats_object(stats)
+    )
+}
+
+fn stats_object(stats: &Stats) -> String {
+    format!(
+        r#"{{"elapsed":{},"searches":{},"searches_with_match":{},"bytes_searched":{},"matched_lines":{},"matches":{}}}"#,
+        duration(stats.elapsed),
+        stats.searches,
+        stats.searches_with_match,
+        stats.bytes_searched,
+        stats.matched_lines,
+        stats.matches
+    )
+}
+
+fn duration(d: Duration) -> String {
+    format!(
+        r#"{{"secs":{},"nanos":{},"human":"{:.6}s"}}"#,
+        d.as_secs(),
+        d.subsec_nanos(),
+        d.as_secs_f64()
+    )
+}
+
+//a json string literal, quotes included
+pub fn string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len() + 2);
+    escaped.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            //every other control char has to be a \u escape
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn escaping() {
+        assert_eq!(r#""say \"hi\"\\n\u0007""#, string("say \"hi\"\\n\u{7}"));
+        assert_eq!("\"tab\\there\\n\"", string("tab\there\n"));
+    }
+
+    #[test]
+    fn match_event() {
+        let m = Match {
+            line_number: 9,
+            byte_offset: 201,
+            line: "To an admiring bog!",
+        };
+        let mut out = Vec::new();
+        line(&mut out, "match", "poem.txt", &m, &[(15, 18)], Labels::default()).unwrap();
+        assert_eq!(
+            concat!(
+                r#"{"type":"match","data":{"path":{"text":"poem.txt"},"lines":{"text":"To an admiring bog!\n"},"#,
+                r#""line_number":9,"absolute_offset":201,"submatches":[{"match":{"text":"bog"},"start":15,"end":18}]}}"#,
+                "\n"
+            ),
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
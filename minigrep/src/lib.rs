@@ -1,60 +1,101 @@
-use std::fs;
 use std::error::Error; //necessary for Box<dyn Error>
-use std::env; 
-
-pub struct Config {
-    pub query: String, //make this String and not &String so we don't have lifetime issues
-    pub file_path: String,
-    pub ignore_case: bool,
-}
-
-impl Config {
-    //doesnt take a reference to self so is Config::build()
-    //note: we call it build() bc people expect new() functions
-    //to never fail
-
-    //good practice to make the build return a Result 
-    pub fn build(args: &[String]) -> Result<Self, &'static str> {
-        //takes in a slice of a sequence containing Strings
-        //returns a Result that is either a Config in the Ok case
-        //or a &str literal of infinite lifetime
-        //we specify the lifetime bc otherwise it would be set to 
-        //the lifetime of the input reference
-
-        //make sure there are enough args
-        if args.len() < 3 {
-            return Err("not enough arguments"); //string literal of infinite lifetime
-        }
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
 
-        //note: as in C, a program by default takes its own name as 
-        //its first command line argument, i.e. args[0]
-        let query: String = args[1].clone(); //clone so we don't have lifetime issues
-        let file_path: String = args[2].clone();
+use printer::{Labels, Printer};
 
-        let ignore_case = env::var("IGNORE_CASE").is_ok(); //is_ok returns false if var isnt set
-        //example use:  IGNORE_CASE=1 cargo run WHO poem.txt 
+mod config; //src/config.rs, the command line parsing
+mod regex; //src/regex.rs, our own little regex engine
+mod casefold; //unicode case folding for -i
+mod aho_corasick; //for searching lots of plain text patterns at once
+mod literal; //searching a buffer for plain text fast
+mod matcher; //the Matcher trait and the plain text one
+mod searcher; //Searcher and Sink, searching without printing
+mod glob;
+mod walk;
+mod printer;
+mod reader;
+mod pool; //the ThreadPool from the hello crate
+mod parallel;
+mod json;
+mod inplace; //--in-place, rewriting files through a temp file
+mod gzip; //-z, our own gzip decompression
+mod follow; //--follow, searching logs as they're written
+mod index; //minigrep index build and --index
+mod fuzzy; //--fuzzy, matching within a number of edits
+mod records; //--csv, --tsv and --jsonl, matching one field of every record
+mod encoding; //utf-16, latin-1 and windows-1252 input, decoded to utf-8
+#[cfg(test)]
+mod tempdir; //directories for tests that clean up after themselves
+pub use config::{ColorChoice, Config, ConfigError, OutputMode};
+pub use encoding::Encoding;
+pub use fuzzy::{Fuzzy, FuzzyMatch};
+pub use matcher::{FindIter, Literal, Matcher};
+pub use searcher::{BinaryFiles, Searcher, Sink};
+pub use records::Field;
+pub use glob::GlobError;
+pub use regex::{escape, Regex, RegexError};
+pub use walk::{WalkError, Walker};
+pub use index::{build_index, IndexStats};
 
-        Ok(Config { 
-            query, 
-            file_path,
-            ignore_case,
-        })
+//how a run went, which main turns into grep's exit status
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Matched, //at least one line was selected
+    NoMatch,
+    Error, //some file couldn't be searched
+}
+
+impl Outcome {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Outcome::Matched => 0,
+            Outcome::NoMatch => 1,
+            Outcome::Error => 2,
+        }
     }
 }
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    //read file contents
+//numbers about how a search went, reported per file and in total by --json
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Stats {
+    pub elapsed: Duration,
+    pub searches: usize,
+    pub searches_with_match: usize,
+    pub bytes_searched: usize,
+    pub matched_lines: usize, //really the selected lines, so with -v it counts the ones that didn't match
+    pub matches: usize,       //individual matches, a line can have several
+}
+
+impl Stats {
+    fn add(&mut self, other: &Stats) {
+        self.elapsed += other.elapsed;
+        self.searches += other.searches;
+        self.searches_with_match += other.searches_with_match;
+        self.bytes_searched += other.bytes_searched;
+        self.matched_lines += other.matched_lines;
+        self.matches += other.matches;
+    }
+}
+
+//Err is only for problems that stop the whole run, like a bad pattern.
+//a file that can't be read gets reported and skipped, and shows up
+//in the Outcome instead
+pub fn run(config: Config) -> Result<Outcome, Box<dyn Error>> {
     //the Box<dyn Error> is a trait object and allows us to return
     //any error we want as long as it implements the Error trait
+    let start = Instant::now();
 
-    let contents: String = fs::read_to_string(config.file_path)?;
-    //the ? like unwrap_or_else but inside the else you end up 
-    //returning Err(err)
+    //turn the paths into a flat list of files, walking any directories
+    let walker = Walker::build(&config.include, &config.exclude)?;
+    let files = walker.files(&config.paths);
 
-    // .expect("Should have been able to find file");
-    // //read_to_string returns a Result<String, io:Error>. expect
-    // //unwraps the String contained in the Ok variant
-    // //and panics if it is an Err variant
+    //like grep, only say which file a line came from when there
+    //could be more than one
+    let with_file_name = files.len() > 1 || config.paths.iter().any(|p| Path::new(p).is_dir());
 
     //println!("Text: \n {}", contents);
 
@@ -64,53 +105,404 @@ pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
     //     let result: Vec<&str> = search(&config.query, &contents);
     // } DOESNT WORK bc results goes out of scope
 
-    let result = if config.ignore_case {
-        search_case_insensitive(&config.query, &contents)
-    } else {
-        search(&config.query, &contents)
+    //compile the patterns once up front instead of once per line. a bad
+    //pattern comes back as a RegexError, which ? boxes up for us. with a
+    //few thousand patterns from -f it helps to know which one it was
+    //--fuzzy patterns are plain text however they look, there's nothing to get wrong
+    let re = match config.fuzzy {
+        Some(max_edits) => Regex::fuzzy(&config.patterns, max_edits, config.ignore_case),
+        None => match Regex::build_many(&patterns(&config), config.ignore_case) {
+            Ok(re) => re,
+            Err(err) if config.patterns.len() > 1 => {
+                return Err(match err.pattern {
+                    Some(i) => format!("{err} in pattern {:?}", config.patterns[i]).into(),
+                    //too big for the vm together. plain text ones go to the automaton instead
+                    None => format!("{err}, try -F if the patterns are plain text").into(),
+                })
+            }
+            Err(err) => return Err(err.into()),
+        },
+    };
+
+    //after with_file_name, so the output looks the same as without --index
+    let files = match config.index {
+        true => index::narrow(files, &re, &config)?,
+        false => files,
     };
-    
-    for line in &result {
-        println!("{}", line);
+
+    //auto only colors for a person looking at a terminal, and the
+    //NO_COLOR convention (no-color.org) turns that off too
+    let color = match config.color {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+    };
+
+    let mut printer = Printer::new(
+        with_file_name,
+        config.line_number,
+        config.byte_offset,
+        config.before_context,
+        config.after_context,
+        color,
+    );
+    printer.json = config.json;
+
+    //--follow never gets to the end of its files, so every one of them
+    //gets a thread whatever -j says. they print straight to stdout, which
+    //mustn't be locked here then
+    let mut tally = Tally::default();
+    if config.follow {
+        follow::search_files(files, &re, &config, &printer, &mut tally);
+        if config.json && config.mode != OutputMode::Quiet {
+            json::summary(&mut io::stdout().lock(), &tally.stats, start.elapsed())?;
+        }
+        return Ok(tally.outcome(&config));
     }
 
-    Ok(())
+    //lock stdout once instead of on every println!
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    //-j picks the number of threads, otherwise use one per cpu. one file
+    //can't be split up, so there's no point in more threads than files
+    let threads = config
+        .threads
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+        .min(files.len());
 
+    if threads > 1 && files.len() > 1 {
+        parallel::search_files(files, &re, &config, &printer, threads, &mut out, &mut tally);
+    } else {
+        for file in files {
+            let result = match file {
+                Ok(file) => search_file(&file, &re, &config, &mut printer, &mut out).map_err(|err| (file, err)),
+                Err(err) => Err((err.path, err.err)),
+            };
+            if !tally.record(result, &config) {
+                break;
+            }
+        }
+    }
+
+    if config.json && config.mode != OutputMode::Quiet {
+        json::summary(&mut out, &tally.stats, start.elapsed())?;
+    }
+    Ok(tally.outcome(&config))
 }
 
-fn search<'a>(query: &'a str, contents: &'a str) -> Vec<&'a str> {
-    //vec!["hello world"]
-    let mut results: Vec<&str> = Vec::new();
+//keeps score as the result of each file comes in
+#[derive(Debug, Default)]
+struct Tally {
+    matched: bool,
+    had_error: bool,
+    stats: Stats,
+}
 
-    for line in contents.lines() {
-        if line.contains(query) {
-            results.push(line);
+impl Tally {
+    //returns false once there's no point in searching any more files
+    fn record(&mut self, result: Result<Stats, (PathBuf, io::Error)>, config: &Config) -> bool {
+        match result {
+            Ok(stats) => {
+                self.matched |= stats.matched_lines > 0;
+                self.stats.add(&stats);
+            }
+            //nobody is reading our output anymore (minigrep ... | head), so stop quietly
+            Err((_, err)) if err.kind() == io::ErrorKind::BrokenPipe => return false,
+            Err((path, err)) => {
+                self.had_error = true;
+                if !config.no_messages {
+                    eprintln!("minigrep: {}: {err}", path.display());
+                }
+            }
         }
+
+        //with -q the answer is known as soon as anything matches
+        !(self.matched && config.mode == OutputMode::Quiet)
     }
-    // or
-    // contents.
-    //      .lines()
-    //      .filter(|line| line.contains(query))
-    //      .collect()
 
-    results
+    fn outcome(&self, config: &Config) -> Outcome {
+        //like grep, an error beats a match, except with -q where a match is all that counts
+        if self.had_error && !(self.matched && config.mode == OutputMode::Quiet) {
+            Outcome::Error
+        } else if self.matched {
+            Outcome::Matched
+        } else {
+            Outcome::NoMatch
+        }
+    }
 }
 
-fn search_case_insensitive<'a>(query: &'a str, contents: &'a str) -> Vec<&'a str> {
-    //vec!["hello world"]
-    let mut results: Vec<&str> = Vec::new();
-    let lowercase_query = query.to_lowercase(); //creates a String, not a slice bc creates new data
+//searches a single file (or stdin for "-") and prints whatever the output mode wants
+fn search_file(
+    file: &Path,
+    re: &Regex,
+    config: &Config,
+    printer: &mut Printer,
+    out: &mut impl Write,
+) -> io::Result<Stats> {
+    search_with(&display_name(file), re, config, printer, out, |searcher, sink| {
+        match (&config.in_place, &config.replace) {
+            //the replaced lines go back into the file instead of to out
+            (Some(backup_suffix), Some(replacement)) => {
+                let start = Instant::now();
+                let mut stats = inplace::rewrite(file, re, replacement, backup_suffix, config.binary_files)?;
+                stats.elapsed = start.elapsed();
+                sink.finish(&stats)?;
+                Ok(stats)
+            }
+            _ => searcher.search_path(re, file, sink),
+        }
+    })
+}
+
+//what a file is called in the output
+fn display_name(file: &Path) -> String {
+    if file == Path::new("-") {
+        reader::STDIN_NAME.to_string()
+    } else {
+        file.display().to_string()
+    }
+}
 
+//everything about searching a file but the searching itself, which
+//`search` does with a Searcher and a sink set up for the config.
+//search_file searches a path, --follow a file that keeps growing
+fn search_with<W, F>(name: &str, re: &Regex, config: &Config, printer: &mut Printer, out: &mut W, search: F) -> io::Result<Stats>
+where
+    W: Write,
+    F: FnOnce(&Searcher, &mut CliSink<'_, W>) -> io::Result<Stats>,
+{
+    //context only means something when lines get printed
+    let lines = config.mode == OutputMode::Lines;
+    let searcher = Searcher {
+        invert_match: config.invert_match,
+        multi_line: config.multiline,
+        before_context: if lines { config.before_context } else { 0 },
+        after_context: if lines { config.after_context } else { 0 },
+        search_zip: config.search_zip,
+        binary: config.binary_files,
+        field: config.field.clone(),
+        encoding: config.encoding,
+    };
 
-    for line in contents.lines() {
-        if line.to_lowercase().contains(&lowercase_query) {
-            results.push(line);
+    printer.begin_file();
+    let mut sink = CliSink {
+        re,
+        config,
+        printer,
+        out,
+        name,
+        matches: 0,
+    };
+    //the ? like unwrap_or_else but inside the else you end up
+    //returning Err(err)
+    let stats = search(&searcher, &mut sink)?;
+    Ok(Stats {
+        matches: stats.matches + sink.matches,
+        ..stats
+    })
+}
+
+//the command line's output: lines for the default mode, names or counts
+//for the others. the modes that only care whether anything matched stop
+//the search at the first selected line, so their stats stop there too
+struct CliSink<'a, W: Write> {
+    re: &'a Regex,
+    config: &'a Config,
+    printer: &'a mut Printer,
+    out: &'a mut W,
+    name: &'a str,
+    matches: usize, //the searcher doesn't count these, see Searcher::search_reader
+}
+
+impl<W: Write> CliSink<'_, W> {
+    //a selected line, a record whose field (the byte range in it) is what
+    //matched, or a -U block and the matches the searcher found in it. the
+    //matches to highlight, replace or report are only the ones in the field
+    fn selected(&mut self, line: &Match, field: Option<(usize, usize)>, block: Option<&[(usize, usize)]>) -> io::Result<bool> {
+        let config = self.config;
+        match config.mode {
+            OutputMode::Lines => {}
+            OutputMode::Count => return Ok(true),
+            //-l, -L and -q are answered by the first selected line
+            _ => return Ok(false),
         }
+
+        let (start, end) = field.unwrap_or((0, line.line.len()));
+        //a match in a block can end with the newline after it, which has to
+        //be there for the match (and its groups) to be what they were
+        let with_newline;
+        let text = match block {
+            Some(_) => {
+                with_newline = format!("{}\n", line.line);
+                &with_newline
+            }
+            None => &line.line[start..end],
+        };
+        let shift = |spans: Vec<(usize, usize)>| spans.into_iter().map(|(s, e)| (start + s, start + e)).collect();
+
+        //--fuzzy is about the closest match on the line, not the leftmost one
+        let closest = match config.invert_match {
+            false => self.re.best_fuzzy_at(text, 0),
+            true => None,
+        };
+        //the first pattern that matched, for --show-pattern
+        let pattern = match (config.show_pattern && !config.invert_match, closest) {
+            (false, _) => None,
+            (true, Some(closest)) => Some(config.patterns[closest.pattern].as_str()),
+            (true, None) => self.re.find_pattern_at(text, 0).map(|(_, _, i)| config.patterns[i].as_str()),
+        };
+        let labels = Labels {
+            pattern,
+            distance: closest.map(|closest| closest.distance),
+        };
+
+        match &config.replace {
+            //the replacements get highlighted instead of the matches
+            Some(replacement) if !config.invert_match => {
+                let (replaced, spans) = match block {
+                    Some(matches) => self.re.replace_matches(text, matches, replacement),
+                    None => self.re.replace_all(text, replacement),
+                };
+                self.matches += spans.len();
+                let spans = if self.printer.color { shift(spans) } else { Vec::new() };
+                let replaced = match (field, block) {
+                    (Some(_), _) => format!("{}{replaced}{}", &line.line[..start], &line.line[end..]),
+                    //the newline is printed after the line anyway, if a match didn't take it
+                    (_, Some(_)) => replaced.strip_suffix('\n').map(str::to_string).unwrap_or(replaced),
+                    (None, None) => replaced,
+                };
+                let line = Match {
+                    line: &replaced,
+                    ..*line
+                };
+                self.printer.selected_line(self.out, self.name, &line, &spans, labels)?
+            }
+            _ => {
+                //only worth finding where on the line the matches are if
+                //they're getting highlighted or reported. with -v there are none
+                let spans: Vec<(usize, usize)> = if let Some(matches) = block {
+                    matches.to_vec()
+                } else if let Some(closest) = closest {
+                    shift([(closest.start, closest.end)].into_iter().filter(|(start, end)| start < end).collect())
+                } else if (self.printer.color || self.printer.json) && !config.invert_match {
+                    shift(self.re.find_iter(text).filter(|(start, end)| start < end).collect())
+                } else {
+                    Vec::new()
+                };
+                self.matches += spans.len();
+                self.printer.selected_line(self.out, self.name, line, &spans, labels)?
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl<W: Write> Sink for CliSink<'_, W> {
+    fn matched(&mut self, line: &Match) -> io::Result<bool> {
+        self.selected(line, None, None)
+    }
+
+    fn matched_field(&mut self, record: &Match, field: (usize, usize)) -> io::Result<bool> {
+        self.selected(record, Some(field), None)
+    }
+
+    fn matched_block(&mut self, block: &Match, matches: &[(usize, usize)]) -> io::Result<bool> {
+        self.selected(block, None, Some(matches))
+    }
+
+    //grep's "Binary file X matches" instead of the line, which is all
+    //there is to say about the file. the other modes don't show lines anyway
+    fn binary_matched(&mut self, line: &Match) -> io::Result<bool> {
+        if self.config.mode != OutputMode::Lines {
+            return self.matched(line);
+        }
+        self.printer.binary_file(self.out, self.name)?;
+        Ok(false)
+    }
+
+    fn context(&mut self, line: &Match) -> io::Result<bool> {
+        self.printer.context_line(self.out, self.name, line)?;
+        Ok(true)
     }
 
-    results
+    fn context_break(&mut self) -> io::Result<()> {
+        self.printer.separator(self.out)
+    }
+
+    fn finish(&mut self, stats: &Stats) -> io::Result<()> {
+        let stats = Stats {
+            matches: stats.matches + self.matches,
+            ..*stats
+        };
+        let count = stats.matched_lines;
+        let (printer, out, name) = (&mut *self.printer, &mut *self.out, self.name);
+
+        match self.config.mode {
+            OutputMode::Lines => printer.end_file(out, name, &stats),
+            OutputMode::Count if printer.with_file_name => printer.file_line(out, name, Some(count)),
+            OutputMode::Count => writeln!(out, "{count}"),
+            OutputMode::FilesWithMatches if count > 0 => printer.file_line(out, name, None),
+            OutputMode::FilesWithoutMatch if count == 0 => printer.file_line(out, name, None),
+            _ => Ok(()),
+        }
+    }
 }
 
+//the patterns as the regex engine gets them, in the same order as
+//config.patterns so a match's pattern index points back at the original
+fn patterns(config: &Config) -> Vec<String> {
+    config
+        .patterns
+        .iter()
+        .map(|pattern| {
+            //-F patterns are escaped so every char in them is a literal
+            let pattern = if config.fixed_strings {
+                regex::escape(pattern)
+            } else {
+                pattern.clone()
+            };
+
+            //-x beats -w like in grep. -w is grep's idea of a word match: no word
+            //char right before or after the match, which isn't the same as \b when
+            //the pattern itself starts or ends with punctuation (-w @foo)
+            let pattern = if config.line_regexp && config.multiline {
+                format!("^(?:{pattern})$")
+            } else if config.line_regexp {
+                format!(r"\A(?:{pattern})\z")
+            } else if config.word_regexp {
+                format!(r"\b{{start-half}}(?:{pattern})\b{{end-half}}")
+            } else {
+                pattern
+            };
+
+            //with -U the text is the whole file, so ^ and $ (and -x) have to go
+            //by the lines in it to mean what they mean without -U
+            match config.multiline {
+                true => format!("(?m:{pattern})"),
+                false => pattern,
+            }
+        })
+        .collect()
+}
+
+//what search hands back for every matching line, so the caller knows
+//where in the file it was and not just what it said
+#[derive(Debug, Clone, PartialEq)]
+pub struct Match<'a> {
+    pub line_number: usize, //starts at 1 like grep -n
+    pub byte_offset: usize, //where the line starts in the file
+    pub line: &'a str,      //without the line ending
+}
+
+impl Match<'_> {
+    /// The line a match ends on. That's the line it starts on, except for
+    /// the blocks of several lines a Searcher finds with `multi_line` (-U).
+    pub fn last_line_number(&self) -> usize {
+        self.line_number + literal::count(b'\n', self.line.as_bytes())
+    }
+}
 
 //let's implement the search feature of our program 
 //by using Test Driven Development. we write a test
@@ -122,6 +514,25 @@ fn search_case_insensitive<'a>(query: &'a str, contents: &'a str) -> Vec<&'a str
 mod test {
     use super::*;
 
+    //the selected lines of contents as (line number, byte offset, line)
+    fn search(re: &Regex, contents: &str, invert_match: bool) -> Vec<(usize, usize, String)> {
+        let searcher = Searcher {
+            invert_match,
+            ..Searcher::new()
+        };
+        let mut selected = Vec::new();
+        let mut sink = |m: &Match| {
+            selected.push((m.line_number, m.byte_offset, m.line.to_string()));
+            Ok(true)
+        };
+        searcher.search_str(re, contents, &mut sink).unwrap();
+        selected
+    }
+
+    fn lines(selected: Vec<(usize, usize, String)>) -> Vec<String> {
+        selected.into_iter().map(|(_, _, line)| line).collect()
+    }
+
     #[test]
     fn search_output() {
         let query: &str = "duct";
@@ -130,7 +541,8 @@ Rust:
 safe, fast, productive.
 Pick three.";
 
-        assert_eq!(vec!["safe, fast, productive."], search(query, contents));
+        let re = Regex::build(query, false).unwrap();
+        assert_eq!(vec!["safe, fast, productive."], lines(search(&re, contents, false)));
     }
 
     #[test]
@@ -141,6 +553,114 @@ Rust:
 safe, fast, productive.
 Pick three.";
 
-        assert_eq!(vec!["Rust:"], search_case_insensitive(query, contents));
+        let re = Regex::build(query, true).unwrap();
+        assert_eq!(vec!["Rust:"], lines(search(&re, contents, false)));
+    }
+
+    #[test]
+    fn regex_query() {
+        let query: &str = r"^\w+,|three\.$";
+        let contents: &str = "\
+Rust:
+safe, fast, productive.
+Pick three.";
+
+        let re = Regex::build(query, false).unwrap();
+        assert_eq!(vec!["safe, fast, productive.", "Pick three."], lines(search(&re, contents, false)));
+    }
+
+    #[test]
+    fn match_positions() {
+        let contents: &str = "Rust:\r\nsafe, fast, productive.\r\nPick three.";
+
+        let re = Regex::build("three", false).unwrap();
+        assert_eq!(vec![(3, 32, "Pick three.".to_string())], search(&re, contents, false));
+        //a needle with a newline in it is never on a line
+        let re = Regex::build(r"fast, productive\.\r\nPick", false).unwrap();
+        assert_eq!(None, re.literal());
+        assert!(search(&re, contents, false).is_empty());
+    }
+
+    #[test]
+    fn inverted() {
+        let contents: &str = "\
+Rust:
+safe, fast, productive.
+Pick three.";
+
+        let re = Regex::build("e", false).unwrap();
+        assert_eq!(vec!["Rust:"], lines(search(&re, contents, true)));
+    }
+
+    #[test]
+    fn several_patterns_and_words() {
+        let args: Vec<String> = ["minigrep", "-w", "-e", "fast|slow", "-e", "Pick", "poem.txt"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let config = Config::from_args(&args).unwrap();
+        let re = Regex::build_many(&patterns(&config), false).unwrap();
+
+        assert!(re.is_match("safe, fast, productive."));
+        assert!(re.is_match("Pick three."));
+        assert!(!re.is_match("Picky, faster"));
+    }
+
+    fn combined(flags: &[&str], ignore_case: bool) -> Regex {
+        let args: Vec<String> = ["minigrep"].iter().chain(flags).map(|s| s.to_string()).collect();
+        let config = Config::from_args(&args).unwrap();
+        Regex::build_many(&patterns(&config), ignore_case).unwrap()
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn words_lines_and_fixed_strings() {
+        //unicode word chars count, and punctuation at the edge of the pattern is fine
+        assert!(!combined(&["-w", "caf"], false).is_match("café au lait"));
+        assert!(combined(&["-w", "@rust"], false).is_match("thanks @rust!"));
+        assert!(combined(&["-w", "rust"], false).is_match("rustacean, rust"));
+
+        assert!(combined(&["-x", "pick three."], true).is_match("Pick three."));
+        assert!(!combined(&["-x", "three"], false).is_match("Pick three."));
+        assert!(combined(&["-x", "-e", "a", "-e", "b"], false).is_match("b"));
+
+        let fixed = combined(&["-F", "-i", "A.B(c)"], true);
+        assert!(fixed.is_match("x a.b(C) y"));
+        assert!(!fixed.is_match("axb(c)"));
+        assert!(combined(&["-Fwx", "a.b"], false).is_match("a.b"));
+    }
+
+    #[test]
+    fn multiline_anchors() {
+        //^ and $ are about lines even though the text is the whole file
+        let text = "Rust:\nsafe, fast, productive.\nPick three.\n";
+        let re = combined(&["-U", r"^safe.*\n^Pick"], false);
+        assert_eq!(Some((6, 34)), re.find_at(text, 0));
+        let re = combined(&["-Ux", "-e", "rust:", "-e", r"Pick \w+."], true);
+        assert_eq!(vec![(0, 5), (30, 41)], re.find_iter(text).collect::<Vec<_>>());
+        assert!(!combined(&["-Ux", "safe"], false).is_match(text));
+    }
+
+    //what -U prints for `text` with these flags
+    fn multiline_output(flags: &[&str], json: bool) -> String {
+        let args: Vec<String> = ["minigrep", "-U"].iter().chain(flags).map(|s| s.to_string()).collect();
+        let config = Config::from_args(&args).unwrap();
+        let re = Regex::build_many(&patterns(&config), false).unwrap();
+        let mut printer = Printer::new(false, false, false, 0, 0, false);
+        printer.json = json;
+        let mut out = Vec::new();
+        let text = "xa\nb\na\n";
+        search_with("t", &re, &config, &mut printer, &mut out, |searcher, sink| searcher.search_str(&re, text, sink)).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn multiline_match_ending_in_a_newline() {
+        //the newline is the end of the match, not a line of the block
+        assert_eq!("xX\nX\n", multiline_output(&["--replace", "X", r"a\n"], false));
+        assert_eq!("x[a]\n[a]\n", multiline_output(&["--replace", "[$1]", r"(a)\n"], false));
+        let json = multiline_output(&[r"a\n"], true);
+        let first = r#""lines":{"text":"xa\n"},"line_number":1,"absolute_offset":0,"submatches":[{"match":{"text":"a\n"},"start":1,"end":3}]"#;
+        assert!(json.contains(first), "{json}");
+        assert!(json.contains(r#""matched_lines":2,"matches":2"#), "{json}");
+    }
+}
@@ -0,0 +1,189 @@
+use std::mem;
+
+//searching a whole buffer for one plain string, instead of splitting it
+//into lines and searching every line. most lines don't match, so most of
+//the time goes into skipping over text, and that's what these are for.
+//
+//memchr and friends look at a usize (8 bytes) at a time with the usual
+//bit tricks instead of one byte at a time. it's not real simd but it's
+//what you can do with only std, and it's several times faster than a
+//plain loop
+
+const WORD: usize = mem::size_of::<usize>();
+const LO: usize = usize::from_ne_bytes([0x01; WORD]); //0x0101...01
+const HI: usize = LO * 0x80; //0x8080...80
+
+fn word_at(hay: &[u8], i: usize) -> usize {
+    usize::from_le_bytes(hay[i..i + WORD].try_into().unwrap())
+}
+
+//true if any byte of x is zero
+fn has_zero(x: usize) -> bool {
+    x.wrapping_sub(LO) & !x & HI != 0
+}
+
+//a mask with the high bit set in exactly the lanes of x that are zero
+fn zero_lanes(x: usize) -> usize {
+    let seven = LO * 0x7f;
+    //the high bit of every lane ends up set unless the lane was zero
+    !(((x & seven) + seven) | x) & HI
+}
+
+/// The index of the first `byte` in `hay`.
+pub fn memchr(byte: u8, hay: &[u8]) -> Option<usize> {
+    let splat = LO * byte as usize; //byte repeated in every lane, xor makes the matching lanes zero
+    let mut i = 0;
+    while i + WORD <= hay.len() && !has_zero(word_at(hay, i) ^ splat) {
+        i += WORD;
+    }
+    hay[i..].iter().position(|&b| b == byte).map(|at| i + at)
+}
+
+/// The index of the last `byte` in `hay`.
+pub fn memrchr(byte: u8, hay: &[u8]) -> Option<usize> {
+    let splat = LO * byte as usize;
+    let mut end = hay.len();
+    while end >= WORD && !has_zero(word_at(hay, end - WORD) ^ splat) {
+        end -= WORD;
+    }
+    hay[..end].iter().rposition(|&b| b == byte)
+}
+
+/// How many times `byte` appears in `hay`, which is how line numbers are
+/// kept up to date over the lines that get skipped.
+pub fn count(byte: u8, hay: &[u8]) -> usize {
+    let splat = LO * byte as usize;
+    let mut total = 0;
+    //every lane keeps its own count, a lane holds up to 255 so they get
+    //added up every 255 words
+    let mut chunks = hay.chunks_exact(255 * WORD);
+    for chunk in &mut chunks {
+        let mut lanes = 0;
+        for i in (0..chunk.len()).step_by(WORD) {
+            lanes += zero_lanes(word_at(chunk, i) ^ splat) >> 7;
+        }
+        total += lanes.to_ne_bytes().iter().map(|&n| n as usize).sum::<usize>();
+    }
+    total + chunks.remainder().iter().filter(|&&b| b == byte).count()
+}
+
+//bytes in the kind of text people grep (english, code, logs), roughly
+//from most to least common. the finder keys on the needle's two least
+//common bytes, and anything not in here is rarer than everything in it
+const BY_FREQUENCY: &[u8] = b" etaoinsrhl\ndcumfp.gwy,b\t0v1_2-/=\";k:()3x54'6j987q>z<[]{}*#$@!%&|+?^`~\\";
+
+fn commonness(b: u8) -> usize {
+    //capitals are about as rare as the rarer lowercase letters
+    let b = if b.is_ascii_uppercase() { b'k' } else { b };
+    BY_FREQUENCY.iter().position(|&c| c == b).map_or(0, |i| BY_FREQUENCY.len() - i)
+}
+
+/// Finds a needle in a haystack of bytes. Two bytes of the needle (the
+/// two rarest ones) are checked at 8 positions at once: a position is
+/// only a candidate if both bytes are where they should be, and only
+/// candidates get the whole needle compared. That's the "generic simd"
+/// substring search, done with usize words instead of simd registers.
+/// Words without the rarest byte are skipped as fast as memchr skips them.
+#[derive(Debug, Clone)]
+pub struct Finder {
+    needle: Vec<u8>,
+    first: usize, //index of the rarest byte of the needle
+    second: usize, //index of the next rarest one, a different position
+}
+
+impl Finder {
+    pub fn new(needle: &[u8]) -> Finder {
+        let mut by_rarity: Vec<usize> = (0..needle.len()).collect();
+        by_rarity.sort_by_key(|&i| commonness(needle[i])); //stable, so ties go to the earlier byte
+
+        Finder {
+            needle: needle.to_vec(),
+            first: by_rarity.first().copied().unwrap_or(0),
+            second: by_rarity.get(1).copied().unwrap_or(0),
+        }
+    }
+
+    pub fn needle_len(&self) -> usize {
+        self.needle.len()
+    }
+
+    /// The index of the first occurrence of the needle in `hay`.
+    pub fn find(&self, hay: &[u8]) -> Option<usize> {
+        let n = self.needle.len();
+        if n == 0 {
+            return Some(0);
+        }
+        if n > hay.len() {
+            return None;
+        }
+        if n == 1 {
+            return memchr(self.needle[0], hay);
+        }
+
+        let first = LO * self.needle[self.first] as usize;
+        let second = LO * self.needle[self.second] as usize;
+        let last_start = hay.len() - n; //the last place the needle could start
+        let reach = self.first.max(self.second); //how far past a start the two bytes can be
+
+        let mut start = 0;
+        while start + reach + WORD <= hay.len() {
+            //zero in the lanes where the byte at start + lane + first is the first byte
+            let a = word_at(hay, start + self.first) ^ first;
+            if !has_zero(a) {
+                start += WORD;
+                continue;
+            }
+            let b = word_at(hay, start + self.second) ^ second;
+            let mut candidates = zero_lanes(a) & zero_lanes(b);
+            while candidates != 0 {
+                let at = start + candidates.trailing_zeros() as usize / 8;
+                if at <= last_start && hay[at..at + n] == self.needle[..] {
+                    return Some(at);
+                }
+                candidates &= candidates - 1; //next lane
+            }
+            start += WORD;
+        }
+
+        //the last few positions, too close to the end for a whole word
+        (start..=last_start).find(|&at| hay[at..at + n] == self.needle[..])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bytes() {
+        let hay = b"Rust:\nsafe, fast, productive.\nPick three.\n";
+        assert_eq!(Some(5), memchr(b'\n', hay));
+        assert_eq!(Some(41), memrchr(b'\n', hay));
+        assert_eq!(Some(29), memrchr(b'\n', &hay[..41]));
+        assert_eq!(3, count(b'\n', hay));
+        assert_eq!(None, memchr(b'z', hay));
+        assert_eq!(0, count(b'\xff', hay));
+        //every lane position, to catch off by ones in the word at a time loops
+        for i in 0..40 {
+            let mut hay = vec![b'a'; 40];
+            hay[i] = 0x80;
+            assert_eq!((Some(i), Some(i), 1), (memchr(0x80, &hay), memrchr(0x80, &hay), count(0x80, &hay)));
+        }
+        //long enough for the lanes to be added up a few times
+        let hay = "ab\n".repeat(5000);
+        assert_eq!(5000, count(b'\n', hay.as_bytes()));
+        assert_eq!(5000, count(b'a', &hay.as_bytes()[1..]) + 1);
+    }
+
+    #[test]
+    fn finds_needles() {
+        let hay = b"The quick brown fox jumps over the lazy dog. The end, 42!";
+        for needle in ["The", "the", "dog.", "42!", "x", "e end", "lazy dog", "!"] {
+            let expected = hay.windows(needle.len()).position(|w| w == needle.as_bytes());
+            assert_eq!(expected, Finder::new(needle.as_bytes()).find(hay), "{needle}");
+        }
+        assert_eq!(None, Finder::new(b"cat").find(hay));
+        assert_eq!(None, Finder::new(b"dog. The end, 42!?").find(hay));
+        assert_eq!(Some(0), Finder::new(b"").find(hay));
+    }
+}
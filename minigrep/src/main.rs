@@ -1,7 +1,8 @@
 use std::env;//style: bring in the parent module of the desired function
+use std::path::Path;
 use std::process;
 
-use minigrep::Config; // minigrep is the current crate, and this
+use minigrep::{Config, ConfigError}; // minigrep is the current crate, and this
 //automatically goes to the library file, the root of the library
 //crate's module tree
 
@@ -24,10 +25,36 @@ fn main() {
     //it iterates over into a collection like a Vec
     //also works to turn stuff into another collection
 
+    //minigrep index build DIR isn't a search, it makes the index --index uses
+    if let [_, index, build, dir] = args.as_slice() {
+        if index == "index" && build == "build" {
+            match minigrep::build_index(Path::new(dir)) {
+                Ok(stats) => {
+                    for err in &stats.skipped {
+                        eprintln!("minigrep: {}: {}", err.path.display(), err.err);
+                    }
+                    println!("indexed {} files in {dir} ({} read, {} removed)", stats.files, stats.read, stats.removed);
+                    process::exit(0);
+                }
+                Err(e) => {
+                    eprintln!("minigrep: {dir}: {e}");
+                    process::exit(2);
+                }
+            }
+        }
+    }
+
     let config: Config = Config::build(&args).unwrap_or_else(|err| {
+        //--help and --version come back as "errors" too, but they
+        //go to stdout and aren't a failure
+        if let ConfigError::Help | ConfigError::Version = err {
+            println!("{err}");
+            process::exit(0);
+        }
         eprintln!("Problem parsing arguments: {err}");
+        eprintln!("Try 'minigrep --help' for more information.");
         //e makes it print to stderr
-        process::exit(1); // exit(error code) gives less verbose output than panic!
+        process::exit(2); // exit(error code) gives less verbose output than panic!
     }); // this is a closure, an anonymous function. the |err|
     //is an argument to it
     //it unwraps a Result if it is Ok and otherwise does something else
@@ -35,10 +62,14 @@ fn main() {
     //which is hardcoded to be the thing inside the Err variant
 
     //logic of program
-    if let Err(e) = minigrep::run(config) {
-        eprintln!("Application error {e}");
-        process::exit(1);
-    }//if let says "if the result is an error, do ..."
+    //grep's exit status: 0 for a match, 1 for no match, 2 for trouble
+    match minigrep::run(config) {
+        Ok(outcome) => process::exit(outcome.exit_code()),
+        Err(e) => {
+            eprintln!("Application error {e}");
+            process::exit(2);
+        }
+    }
 }
 
 
@@ -0,0 +1,180 @@
+use crate::aho_corasick::AhoCorasick;
+use crate::literal::Finder;
+use crate::Regex;
+
+//what a Searcher needs from whatever finds the matches: where in a line
+//the next one is. Regex is the one the command line uses, Literal is for
+//callers who only ever look for plain text and don't want to think about
+//escaping it
+
+/// Finds matches inside a single line of text.
+pub trait Matcher {
+    /// The byte range of the leftmost match in `text` starting at or after `start`.
+    fn find_at(&self, text: &str, start: usize) -> Option<(usize, usize)>;
+
+    fn is_match(&self, text: &str) -> bool {
+        self.find_at(text, 0).is_some()
+    }
+
+    /// Every non-overlapping match in `text`, left to right.
+    fn find_iter<'m, 't>(&'m self, text: &'t str) -> FindIter<'m, 't, Self>
+    where
+        Self: Sized,
+    {
+        FindIter::new(self, text)
+    }
+
+    /// The plain text every match is exactly, if there is such a thing. A
+    /// line matches exactly when it contains that text, which lets the
+    /// Searcher skip over the lines in between without looking at them.
+    fn literal(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl Matcher for Regex {
+    fn find_at(&self, text: &str, start: usize) -> Option<(usize, usize)> {
+        Regex::find_at(self, text, start)
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        Regex::is_match(self, text)
+    }
+
+    fn literal(&self) -> Option<&str> {
+        Regex::literal(self)
+    }
+}
+
+/// Plain text, with no regex syntax to escape.
+#[derive(Debug, Clone)]
+pub struct Literal {
+    text: String,
+    finder: Finder,
+    ignore_case: Option<AhoCorasick>, //an automaton with just the one pattern already knows how to fold case
+}
+
+impl Literal {
+    pub fn new(text: &str) -> Literal {
+        Literal {
+            text: text.to_string(),
+            finder: Finder::new(text.as_bytes()),
+            ignore_case: None,
+        }
+    }
+
+    /// Matches `text` in any case, with the same unicode case folding as -i.
+    pub fn ignore_case(text: &str) -> Literal {
+        let chars: Vec<char> = text.chars().collect();
+        Literal {
+            ignore_case: (!chars.is_empty()).then(|| AhoCorasick::new(&[chars], true)),
+            ..Literal::new(text)
+        }
+    }
+}
+
+impl Matcher for Literal {
+    fn find_at(&self, text: &str, start: usize) -> Option<(usize, usize)> {
+        if start > text.len() {
+            return None;
+        }
+        if self.text.is_empty() {
+            return Some((start, start));
+        }
+        match &self.ignore_case {
+            Some(ac) => ac.find_at(text, start, |_, _, _| true).map(|(start, end, _)| (start, end)),
+            None => {
+                let at = start + self.finder.find(&text.as_bytes()[start..])?;
+                Some((at, at + self.text.len()))
+            }
+        }
+    }
+
+    fn literal(&self) -> Option<&str> {
+        //the searcher's fast path works on raw bytes, so only for case sensitive text
+        //that can be on a line at all
+        match self.ignore_case {
+            None if !self.text.is_empty() && !self.text.contains('\n') => Some(&self.text),
+            _ => None,
+        }
+    }
+}
+
+pub struct FindIter<'m, 't, M: ?Sized> {
+    matcher: &'m M,
+    text: &'t str,
+    pos: usize,
+    last_end: Option<usize>,
+}
+
+impl<'m, 't, M: Matcher + ?Sized> FindIter<'m, 't, M> {
+    pub(crate) fn new(matcher: &'m M, text: &'t str) -> FindIter<'m, 't, M> {
+        FindIter {
+            matcher,
+            text,
+            pos: 0,
+            last_end: None,
+        }
+    }
+}
+
+impl<'m, 't, M: Matcher + ?Sized> Iterator for FindIter<'m, 't, M> {
+    type Item = (usize, usize);
+
+    fn next(&mut self) -> Option<(usize, usize)> {
+        loop {
+            if self.pos > self.text.len() {
+                return None;
+            }
+            let (start, end) = self.matcher.find_at(self.text, self.pos)?;
+
+            if start == end {
+                //an empty match has to move us forward by hand or we'd find it forever
+                self.pos = match self.text[end..].chars().next() {
+                    Some(c) => end + c.len_utf8(),
+                    None => end + 1,
+                };
+                //and one right where the last match ended doesn't count (like in "a*" on "ab")
+                if self.last_end == Some(end) {
+                    continue;
+                }
+            } else {
+                self.pos = end;
+            }
+
+            self.last_end = Some(end);
+            return Some((start, end));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn literals() {
+        let plain = Literal::new("a.b");
+        assert_eq!(vec![(2, 5), (10, 13)], plain.find_iter("x a.b axb a.b").collect::<Vec<_>>());
+        assert_eq!(Some("a.b"), plain.literal());
+        assert!(!plain.is_match("A.B"));
+
+        let any_case = Literal::ignore_case("straße");
+        assert_eq!(Some((4, 11)), any_case.find_at("die STRAßE", 0));
+        assert_eq!(None, any_case.find_at("die STRAßE", 5));
+        assert_eq!(None, any_case.literal());
+
+        //an empty literal matches everywhere, like an empty pattern
+        assert_eq!(3, Literal::new("").find_iter("ab").count());
+    }
+
+    #[test]
+    fn regexes_are_matchers() {
+        fn spans(matcher: &impl Matcher, text: &str) -> Vec<(usize, usize)> {
+            matcher.find_iter(text).collect()
+        }
+        let re = Regex::build("a*", false).unwrap();
+        assert_eq!(vec![(0, 1), (2, 2)], spans(&re, "ab"));
+        assert_eq!(Some("three"), Matcher::literal(&Regex::build("three", false).unwrap()));
+    }
+}
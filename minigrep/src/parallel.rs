@@ -0,0 +1,248 @@
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+
+use crate::pool::ThreadPool;
+use crate::printer::Printer;
+use crate::walk::WalkError;
+use crate::{search_file, Config, OutputMode, Regex, Stats, Tally};
+
+//either the stats of a file plus everything it would have printed,
+//or the error that stopped it
+type SearchResult = io::Result<(Stats, Vec<u8>)>;
+
+//what a worker sends back: the file's position in the list, its path
+//(for error messages) and how the search went
+type FileResult = (usize, PathBuf, SearchResult);
+
+//how many files per thread can be searched or waiting to be printed at
+//once. enough that the workers don't sit idle behind one slow file, but
+//one slow file early on doesn't mean the whole tree's output in memory
+const FILES_PER_THREAD: usize = 4;
+
+/// Searches the files on a pool of `threads` workers. Every file is
+/// printed into its own buffer and the buffers are written out strictly
+/// in the order the files were given, so the output is the same as a
+/// single threaded run no matter which worker finishes first. Files only
+/// get handed out a few per thread ahead of the one that's printed next.
+pub fn search_files(
+    files: Vec<Result<PathBuf, WalkError>>,
+    re: &Regex,
+    config: &Config,
+    printer: &Printer,
+    threads: usize,
+    out: &mut impl Write,
+    tally: &mut Tally,
+) {
+    let total = files.len();
+    let re = Arc::new(re.clone());
+    let config = Arc::new(config.clone());
+    let stop = Arc::new(AtomicBool::new(false)); //set once -q has its answer or stdout is gone
+    let (sender, receiver) = mpsc::channel::<FileResult>();
+    let mut sender = Some(sender);
+
+    //results that came in before it was their turn to be printed
+    let mut pending: BTreeMap<usize, (PathBuf, SearchResult)> = BTreeMap::new();
+
+    let pool = ThreadPool::new(threads);
+    let ahead = threads * FILES_PER_THREAD;
+    let mut files = files.into_iter().enumerate();
+    let mut next = 0;
+    let mut printed_any = false;
+    while next < total {
+        //hand out files until there are enough on the go
+        let handed_out = total - files.len();
+        for (index, file) in files.by_ref().take((next + ahead).saturating_sub(handed_out)) {
+            let path = match file {
+                Ok(path) => path,
+                Err(err) => {
+                    //nothing to search, it just needs reporting when its turn comes
+                    pending.insert(index, (err.path, Err(err.err)));
+                    continue;
+                }
+            };
+
+            let (re, config, stop) = (Arc::clone(&re), Arc::clone(&config), Arc::clone(&stop));
+            let sender = sender.clone().expect("files are only handed out while there's a sender");
+            let mut printer = printer.clone(); //a fresh one, so no context leaks between files
+            pool.execute(move || {
+                if stop.load(Ordering::Relaxed) {
+                    return;
+                }
+                let mut buf = Vec::new();
+                let result = search_file(&path, &re, &config, &mut printer, &mut buf).map(|stats| (stats, buf));
+                //the receiver is gone if we stopped early, and then nobody cares
+                let _ = sender.send((index, path, result));
+            });
+        }
+        if files.len() == 0 {
+            sender = None; //so recv() fails instead of hanging if a worker died
+        }
+
+        let (path, result) = match pending.remove(&next) {
+            Some(ready) => ready,
+            None => match receiver.recv() {
+                Ok((index, path, result)) => {
+                    pending.insert(index, (path, result));
+                    continue;
+                }
+                Err(_) => break,
+            },
+        };
+        next += 1;
+
+        let result = result.and_then(|(stats, buf)| {
+            //the workers can't know about each other, so the -- between
+            //context groups of different files gets added here
+            if !buf.is_empty() && config.mode == OutputMode::Lines {
+                if printed_any && printer.has_context() {
+                    printer.separator(out)?;
+                }
+                printed_any = true;
+            }
+            out.write_all(&buf)?;
+            Ok(stats)
+        });
+
+        if !tally.record(result.map_err(|err| (path, err)), &config) {
+            stop.store(true, Ordering::Relaxed);
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tempdir::TempDir;
+    use std::fs;
+    use std::path::Path;
+
+    //searches `files` for "hit" with these flags, giving what got printed
+    fn run(files: Vec<Result<PathBuf, WalkError>>, flags: &[&str], threads: usize, out: &mut impl Write) -> Tally {
+        let args: Vec<String> = ["minigrep"].iter().chain(flags).chain(&["hit"]).map(|s| s.to_string()).collect();
+        let config = Config::from_args(&args).unwrap();
+        let re = Regex::build("hit", false).unwrap();
+        let printer = Printer::new(true, false, false, 0, 0, false);
+        let mut tally = Tally::default();
+        search_files(files, &re, &config, &printer, threads, out, &mut tally);
+        tally
+    }
+
+    //more files than the 2 threads the tests use are allowed to have out at once
+    fn many_files(dir: &Path) -> Vec<PathBuf> {
+        let files: Vec<PathBuf> = (0..FILES_PER_THREAD * 2 * 3).map(|i| dir.join(format!("{i:02}.txt"))).collect();
+        for (i, path) in files.iter().enumerate() {
+            fs::write(path, "hit\n".repeat(i % 3) + "miss\n").unwrap();
+        }
+        files
+    }
+
+    #[test]
+    fn output_keeps_input_order() {
+        let dir = TempDir::new("parallel");
+        let mut files = Vec::new();
+        for i in 0..20 {
+            let path = dir.join(format!("{i}.txt"));
+            //the early files are the biggest, so they tend to finish last
+            fs::write(&path, "line\n".repeat((20 - i) * 500) + "hit\n").unwrap();
+            files.push(path);
+        }
+
+        let args: Vec<String> = ["minigrep", "-n", "hit"].iter().map(|s| s.to_string()).collect();
+        let config = Config::from_args(&args).unwrap();
+        let re = Regex::build("hit", false).unwrap();
+        let printer = Printer::new(true, true, false, 0, 0, false);
+
+        let expected: String = files
+            .iter()
+            .enumerate()
+            .map(|(i, path)| format!("{}:{}:hit\n", path.display(), (20 - i) * 500 + 1))
+            .collect();
+        //with 2 threads only some of the files are out at a time
+        for threads in [2, 4] {
+            let mut out = Vec::new();
+            let mut tally = Tally::default();
+            search_files(files.iter().cloned().map(Ok).collect(), &re, &config, &printer, threads, &mut out, &mut tally);
+            assert_eq!(expected, String::from_utf8(out).unwrap());
+            assert!(tally.matched);
+        }
+    }
+
+    #[test]
+    fn stopping_early() {
+        let dir = TempDir::new("parallel-stop");
+        let files = many_files(&dir);
+        //a file that isn't there, after the first match: it never gets reported
+        let mut with_missing: Vec<_> = files.iter().cloned().map(Ok).collect();
+        with_missing.insert(5, Ok(dir.join("missing.txt")));
+
+        //-q is done with the first file that matches, the ones after it don't count
+        let tally = run(with_missing, &["-q"], 2, &mut Vec::new());
+        assert_eq!((true, false, 2), (tally.matched, tally.had_error, tally.stats.searches));
+
+        //-l stops each file at its first match, but goes on to the next file, in order
+        let mut out = Vec::new();
+        let tally = run(files.iter().cloned().map(Ok).collect(), &["-l"], 2, &mut out);
+        let expected: String = files.iter().enumerate().filter(|(i, _)| i % 3 > 0).map(|(_, path)| format!("{}\n", path.display())).collect();
+        assert_eq!(expected, String::from_utf8(out).unwrap());
+        assert_eq!((files.len(), files.len() / 3 * 2), (tally.stats.searches, tally.stats.matched_lines));
+
+        //nobody reading the output anymore stops it quietly. the first file has no match to write
+        let tally = run(files.iter().cloned().map(Ok).collect(), &[], 2, &mut ClosedAfter(1));
+        assert_eq!((false, 2), (tally.had_error, tally.stats.searches));
+    }
+
+    //a pipe that gets closed once `n` writes have gone through it
+    struct ClosedAfter(usize);
+
+    impl Write for ClosedAfter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.0 == 0 {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            self.0 -= 1;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn errors_and_more_threads_than_files() {
+        let dir = TempDir::new("parallel-errors");
+        let files = many_files(&dir);
+        let unreadable = |name: &str| WalkError {
+            path: dir.join(name),
+            err: io::ErrorKind::PermissionDenied.into(),
+        };
+        let mixed = || {
+            vec![
+                Err(unreadable("first")),
+                Ok(files[1].clone()),
+                Ok(dir.join("missing.txt")),
+                Ok(files[2].clone()),
+                Err(unreadable("last")),
+            ]
+        };
+        let expected = format!("{one}:hit\n{two}:hit\n{two}:hit\n", one = files[1].display(), two = files[2].display());
+
+        //more threads than files, and more files than the threads have out at once
+        for threads in [2, 16] {
+            let mut out = Vec::new();
+            let tally = run(mixed(), &["-s"], threads, &mut out);
+            assert_eq!(expected, String::from_utf8(out).unwrap());
+            assert_eq!((true, true, 2), (tally.matched, tally.had_error, tally.stats.searches));
+
+            let mut out = Vec::new();
+            let tally = run(files.iter().cloned().map(Ok).collect(), &["-c"], threads, &mut out);
+            let counts: Vec<String> = files.iter().enumerate().map(|(i, path)| format!("{}:{}", path.display(), i % 3)).collect();
+            assert_eq!(counts.join("\n") + "\n", String::from_utf8(out).unwrap());
+            assert!(!tally.had_error);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+//this is the ThreadPool from the hello web server (hello/src/lib.rs),
+//minus the println!s: a worker announcing every job it picks up would
+//end up mixed into our search results
+
+struct Worker {
+    thread: Option<thread::JoinHandle<()>>, //option so that we can .take() ownership of it for graceful shutdown
+}
+
+impl Worker {
+    fn new(receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Worker {
+        let thread = thread::spawn(move || loop {
+            //a plain let so the lock is released before the job runs
+            //(see the note at the bottom of hello/src/lib.rs)
+            let message = receiver.lock().expect("worker couldn't get the job queue lock").recv();
+
+            match message {
+                Ok(job) => job(),
+                Err(_) => break, //the pool was dropped, so no more jobs are coming
+            }
+        });
+
+        Worker { thread: Some(thread) }
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send + 'static>; //type alias
+
+pub struct ThreadPool {
+    workers: Vec<Worker>,
+    sender: Option<mpsc::Sender<Job>>, //option so that we can .take() ownership of it for graceful shutdown
+}
+
+impl ThreadPool {
+    /// Create a new ThreadPool with `size` threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the size is zero.
+    pub fn new(size: usize) -> ThreadPool {
+        assert!(size > 0);
+
+        let (sender, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let mut workers = Vec::with_capacity(size);
+        for _ in 0..size {
+            workers.push(Worker::new(Arc::clone(&receiver)));
+        }
+
+        ThreadPool {
+            workers,
+            sender: Some(sender),
+        }
+    }
+
+    pub fn execute<F>(&self, f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let job = Box::new(f);
+
+        self.sender
+            .as_ref()
+            .expect("Sender was None!")
+            .send(job)
+            .expect("Error occurred when sender sent job through channel!");
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        drop(self.sender.take()); //closes the channel so the workers' loops end
+
+        for worker in &mut self.workers {
+            if let Some(thread) = worker.thread.take() {
+                thread.join().expect("Could not join a worker's thread at shutdown");
+            }
+        }
+    }
+}
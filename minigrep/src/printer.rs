@@ -0,0 +1,332 @@
+use std::io::{self, Write};
+
+use crate::json;
+use crate::{Match, Stats};
+
+//the ansi escape codes grep uses by default: bold red matches, magenta
+//file names, green line numbers and offsets and cyan separators
+const MATCH_COLOR: &str = "\x1b[1;31m";
+const PATH_COLOR: &str = "\x1b[35m";
+const NUMBER_COLOR: &str = "\x1b[32m";
+const SEPARATOR_COLOR: &str = "\x1b[36m";
+const RESET: &str = "\x1b[0m";
+
+/// What gets printed about a selected line besides where it is.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Labels<'a> {
+    pub pattern: Option<&'a str>, //--show-pattern, the pattern that matched
+    pub distance: Option<usize>,  //--fuzzy, how many edits the closest match is from its pattern
+}
+
+//everything about how a hit gets shown, as opposed to how it gets found.
+//which lines are context is the Searcher's business, the printer just
+//gets told about them one at a time as the file is read
+#[derive(Clone)]
+pub struct Printer {
+    pub with_file_name: bool,
+    pub line_number: bool, //-n
+    pub byte_offset: bool, //-b
+    pub before: usize,     //-B
+    pub after: usize,      //-A
+    pub color: bool,       //whether --color ended up on
+    pub json: bool,        //--json, write events instead of lines
+    printed_any: bool,     //so groups in different files also get a -- between them
+    //the rest is about the file we're currently in
+    begun: bool, //whether the json begin event for this file is out yet
+    printed_file: bool,
+}
+
+impl Printer {
+    pub fn new(
+        with_file_name: bool,
+        line_number: bool,
+        byte_offset: bool,
+        before: usize,
+        after: usize,
+        color: bool,
+    ) -> Printer {
+        Printer {
+            with_file_name,
+            line_number,
+            byte_offset,
+            before,
+            after,
+            color,
+            json: false,
+            printed_any: false,
+            begun: false,
+            printed_file: false,
+        }
+    }
+
+    pub fn has_context(&self) -> bool {
+        self.before > 0 || self.after > 0
+    }
+
+    //the -- between groups of context lines. json has line numbers to show the gaps
+    pub fn separator(&self, out: &mut impl Write) -> io::Result<()> {
+        if self.json {
+            return Ok(());
+        }
+        self.paint(out, SEPARATOR_COLOR, "--")?;
+        writeln!(out)
+    }
+
+    //like ripgrep, only files that printed something get a json end event
+    pub fn end_file(&mut self, out: &mut impl Write, path: &str, stats: &Stats) -> io::Result<()> {
+        if self.json && self.begun {
+            json::end(out, path, stats)?;
+        }
+        Ok(())
+    }
+
+    pub fn begin_file(&mut self) {
+        self.begun = false;
+        self.printed_file = false;
+    }
+
+    /// Prints a selected line. `spans` are the byte ranges inside the line
+    /// to highlight, and `labels` go after the line number and offset.
+    pub fn selected_line(
+        &mut self,
+        out: &mut impl Write,
+        path: &str,
+        m: &Match,
+        spans: &[(usize, usize)],
+        labels: Labels,
+    ) -> io::Result<()> {
+        self.print_line(out, path, m, ':', spans, labels)
+    }
+
+    pub fn context_line(&mut self, out: &mut impl Write, path: &str, m: &Match) -> io::Result<()> {
+        self.print_line(out, path, m, '-', &[], Labels::default())
+    }
+
+    //grep uses : after the prefixes of matching lines and - for context lines
+    fn print_line(
+        &mut self,
+        out: &mut impl Write,
+        path: &str,
+        line: &Match,
+        sep: char,
+        spans: &[(usize, usize)],
+        labels: Labels,
+    ) -> io::Result<()> {
+        //the searcher puts the -- between groups in one file, this is the one between files
+        if !self.printed_file && self.printed_any && self.has_context() {
+            self.separator(out)?;
+        }
+        self.printed_file = true;
+        self.printed_any = true;
+
+        if self.json {
+            if !self.begun {
+                json::begin(out, path)?;
+                self.begun = true;
+            }
+            let kind = if sep == ':' { "match" } else { "context" };
+            return json::line(out, kind, path, line, spans, labels);
+        }
+
+        //a -U block is several lines, and every one of them gets its own
+        //prefix so the numbers say where the block starts and ends
+        let sep = sep.to_string();
+        let mut offset = 0; //where this line starts in the block
+        for (i, text) in line.line.split('\n').enumerate() {
+            let next = offset + text.len() + 1;
+            let text = text.strip_suffix('\r').unwrap_or(text);
+            if self.with_file_name {
+                self.paint(out, PATH_COLOR, path)?;
+                self.paint(out, SEPARATOR_COLOR, &sep)?;
+            }
+            if self.line_number {
+                self.paint(out, NUMBER_COLOR, &(line.line_number + i).to_string())?;
+                self.paint(out, SEPARATOR_COLOR, &sep)?;
+            }
+            if self.byte_offset {
+                self.paint(out, NUMBER_COLOR, &(line.byte_offset + offset).to_string())?;
+                self.paint(out, SEPARATOR_COLOR, &sep)?;
+            }
+            if let Some(distance) = labels.distance {
+                self.paint(out, NUMBER_COLOR, &distance.to_string())?;
+                self.paint(out, SEPARATOR_COLOR, &sep)?;
+            }
+            if let Some(pattern) = labels.pattern {
+                write!(out, "{pattern}")?;
+                self.paint(out, SEPARATOR_COLOR, &sep)?;
+            }
+
+            //the text between the spans goes out plain, the spans themselves in
+            //color. a span across lines gets cut into one piece per line
+            let mut written = 0;
+            for &(start, end) in spans {
+                let on_line = match start == end {
+                    true => (offset..=offset + text.len()).contains(&start),
+                    false => start < offset + text.len() && end > offset,
+                };
+                if !on_line {
+                    continue;
+                }
+                let start = start.clamp(offset, offset + text.len()) - offset;
+                let end = end.clamp(offset, offset + text.len()) - offset;
+                write!(out, "{}", &text[written..start])?;
+                self.paint(out, MATCH_COLOR, &text[start..end])?;
+                written = end;
+            }
+            writeln!(out, "{}", &text[written..])?;
+            offset = next;
+        }
+        Ok(())
+    }
+
+    //json has no way of saying it, the end event's stats still count the line
+    pub fn binary_file(&mut self, out: &mut impl Write, path: &str) -> io::Result<()> {
+        if self.json {
+            return Ok(());
+        }
+        writeln!(out, "Binary file {path} matches")
+    }
+
+    //the output of -c, -l and -L: a file name, maybe followed by a count
+    pub fn file_line(&self, out: &mut impl Write, path: &str, count: Option<usize>) -> io::Result<()> {
+        self.paint(out, PATH_COLOR, path)?;
+        if let Some(count) = count {
+            self.paint(out, SEPARATOR_COLOR, ":")?;
+            write!(out, "{count}")?;
+        }
+        writeln!(out)
+    }
+
+    fn paint(&self, out: &mut impl Write, color: &str, text: &str) -> io::Result<()> {
+        if self.color {
+            write!(out, "{color}{text}{RESET}")
+        } else {
+            write!(out, "{text}")
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Regex, Searcher, Sink};
+
+    //the smallest sink that prints like the command line does
+    struct Lines<'a> {
+        printer: &'a mut Printer,
+        re: Regex,
+        out: Vec<u8>, //Vec<u8> implements Write, so it can stand in for stdout
+    }
+
+    impl Sink for Lines<'_> {
+        fn matched(&mut self, m: &Match) -> io::Result<bool> {
+            let spans: Vec<(usize, usize)> = self.re.find_iter(m.line).collect();
+            self.printer.selected_line(&mut self.out, "poem.txt", m, &spans, Labels::default())?;
+            Ok(true)
+        }
+
+        fn context(&mut self, m: &Match) -> io::Result<bool> {
+            self.printer.context_line(&mut self.out, "poem.txt", m)?;
+            Ok(true)
+        }
+
+        fn context_break(&mut self) -> io::Result<()> {
+            self.printer.separator(&mut self.out)
+        }
+    }
+
+    fn print(printer: &mut Printer, pattern: &str, contents: &str) -> String {
+        let searcher = Searcher {
+            before_context: printer.before,
+            after_context: printer.after,
+            ..Searcher::new()
+        };
+        printer.begin_file();
+        let mut lines = Lines {
+            printer,
+            re: Regex::build(pattern, false).unwrap(),
+            out: Vec::new(),
+        };
+        searcher.search_str(&lines.re.clone(), contents, &mut lines).unwrap();
+        String::from_utf8(lines.out).unwrap()
+    }
+
+    #[test]
+    fn line_numbers_and_offsets() {
+        let mut printer = Printer::new(true, true, true, 0, 0, false);
+        let out = print(&mut printer, "three", "Rust:\nsafe, fast, productive.\nPick three.\n");
+        assert_eq!("poem.txt:3:30:Pick three.\n", out);
+    }
+
+    #[test]
+    fn matching_pattern() {
+        let mut printer = Printer::new(true, true, false, 0, 0, false);
+        let m = Match {
+            line_number: 3,
+            byte_offset: 29,
+            line: "Pick three.",
+        };
+        let mut out = Vec::new();
+        let labels = Labels {
+            pattern: Some("thr.e"),
+            ..Labels::default()
+        };
+        printer.selected_line(&mut out, "poem.txt", &m, &[], labels).unwrap();
+        assert_eq!("poem.txt:3:thr.e:Pick three.\n", String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn context_groups() {
+        let contents = "1\n2\nhit 3\n4\n5\n6\n7\nhit 8\nhit 9\n10\n";
+        let mut printer = Printer::new(false, true, false, 1, 1, false);
+        let out = print(&mut printer, "hit", contents);
+        assert_eq!("2-2\n3:hit 3\n4-4\n--\n7-7\n8:hit 8\n9:hit 9\n10-10\n", out);
+    }
+
+    #[test]
+    fn multi_line_blocks() {
+        let mut printer = Printer::new(false, true, true, 0, 0, true);
+        let m = Match {
+            line_number: 2,
+            byte_offset: 6,
+            line: "safe, fast,\r\nproductive.",
+        };
+        let mut out = Vec::new();
+        printer.selected_line(&mut out, "poem.txt", &m, &[(6, 20)], Labels::default()).unwrap();
+        //the match is cut in two, and every line has its own number and offset
+        let color = |color: &str, text: &str| format!("{color}{text}{RESET}");
+        let expected = [
+            color(NUMBER_COLOR, "2") + &color(SEPARATOR_COLOR, ":") + &color(NUMBER_COLOR, "6"),
+            color(SEPARATOR_COLOR, ":") + "safe, " + &color(MATCH_COLOR, "fast,") + "\n",
+            color(NUMBER_COLOR, "3") + &color(SEPARATOR_COLOR, ":") + &color(NUMBER_COLOR, "19"),
+            color(SEPARATOR_COLOR, ":") + &color(MATCH_COLOR, "product") + "ive.\n",
+        ];
+        assert_eq!(expected.concat(), String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn overlapping_context() {
+        let contents = "a\nhit\nb\nhit\nc";
+        let mut printer = Printer::new(false, true, false, 2, 2, false);
+        let out = print(&mut printer, "hit", contents);
+        assert_eq!("1-a\n2:hit\n3-b\n4:hit\n5-c\n", out);
+    }
+
+    #[test]
+    fn separator_between_files() {
+        let mut printer = Printer::new(true, false, false, 0, 1, false);
+        let mut out = print(&mut printer, "hit", "hit\nx\n");
+        out += &print(&mut printer, "hit", "hit\n");
+        assert_eq!("poem.txt:hit\npoem.txt-x\n--\npoem.txt:hit\n", out);
+    }
+
+    #[test]
+    fn colors() {
+        let mut printer = Printer::new(true, true, false, 0, 0, true);
+        let out = print(&mut printer, "e+", "Pick three.\n");
+        assert_eq!(
+            "\x1b[35mpoem.txt\x1b[0m\x1b[36m:\x1b[0m\x1b[32m1\x1b[0m\x1b[36m:\x1b[0mPick thr\x1b[1;31mee\x1b[0m.\n",
+            out
+        );
+    }
+}
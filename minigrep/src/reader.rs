@@ -0,0 +1,256 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::str;
+
+use crate::gzip::{self, GzDecoder};
+use crate::literal::{self, Finder};
+use crate::Match;
+
+//how much of the input we read from the os at a time. lines are pulled
+//out of this buffer one by one, so memory use depends on the longest
+//line and not on the size of the file
+const BUFFER_SIZE: usize = 64 * 1024;
+
+//what grep calls stdin in its output
+pub const STDIN_NAME: &str = "(standard input)";
+
+/// Opens a path for reading, where "-" means stdin. With `decompress`
+/// (-z) a gzip file is decompressed as it's read, anything that isn't
+/// compressed is read as it is.
+pub fn open(path: &str, decompress: bool) -> io::Result<Box<dyn BufRead>> {
+    let input: Box<dyn Read> = if path == "-" {
+        Box::new(io::stdin())
+    } else {
+        Box::new(File::open(path)?)
+    };
+    let mut reader = BufReader::with_capacity(BUFFER_SIZE, input);
+    if !decompress {
+        return Ok(Box::new(reader));
+    }
+
+    //the file decides, not its name: rotated logs aren't always called .gz
+    match gzip::format(reader.fill_buf()?) {
+        Some("gzip") => Ok(Box::new(BufReader::with_capacity(BUFFER_SIZE, GzDecoder::new(reader)))),
+        Some(format) => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{format} compressed files aren't supported, only gzip"),
+        )),
+        None => Ok(Box::new(reader)),
+    }
+}
+
+/// Whether `bytes` (the start of a file) looks like binary data rather
+/// than text: it has a NUL byte, or bytes that aren't utf-8. A utf-8
+/// sequence cut off at the very end doesn't count, the rest of it is
+/// just in the next read.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    literal::memchr(0, bytes).is_some() || str::from_utf8(bytes).is_err_and(|err| err.error_len().is_some())
+}
+
+//hands out one line at a time from any BufRead (a file, stdin, a &[u8] in
+//tests), keeping track of line numbers and byte offsets as it goes.
+//the same two buffers are reused for every line so there is no
+//allocation per line once they've grown big enough
+pub struct LineReader<R: BufRead> {
+    reader: R,
+    buf: Vec<u8>,
+    lossy: String, //the decoded line, only used when it isn't valid utf-8
+    saw_invalid_utf8: bool,
+    offset: usize,
+    line_number: usize,
+}
+
+impl<R: BufRead> LineReader<R> {
+    pub fn new(reader: R) -> LineReader<R> {
+        LineReader {
+            reader,
+            buf: Vec::new(),
+            lossy: String::new(),
+            saw_invalid_utf8: false,
+            offset: 0,
+            line_number: 0,
+        }
+    }
+
+    pub fn bytes_read(&self) -> usize {
+        self.offset
+    }
+
+    /// Whether any line so far wasn't valid utf-8.
+    pub fn saw_invalid_utf8(&self) -> bool {
+        self.saw_invalid_utf8
+    }
+
+    /// Reads the next line. The returned Match borrows from the reader, so
+    /// it has to be dropped before asking for the line after it. Invalid
+    /// utf-8 is replaced with U+FFFD instead of being an error, which is
+    /// how logs with the odd stray byte in them still get searched.
+    pub fn next_line(&mut self) -> io::Result<Option<Match<'_>>> {
+        match self.read_line()? {
+            Some(byte_offset) => Ok(Some(self.line(byte_offset))),
+            None => Ok(None),
+        }
+    }
+
+    //reads the next line into buf and returns where it starts
+    fn read_line(&mut self) -> io::Result<Option<usize>> {
+        self.buf.clear();
+        let read = self.reader.read_until(b'\n', &mut self.buf)?;
+        if read == 0 {
+            return Ok(None);
+        }
+
+        let byte_offset = self.offset;
+        self.offset += read;
+        self.line_number += 1;
+        Ok(Some(byte_offset))
+    }
+
+    //how much of buf is the line itself, without the \n or \r\n
+    fn line_end(&self) -> usize {
+        let mut end = self.buf.len();
+        if self.buf[..end].ends_with(b"\n") {
+            end -= 1;
+        }
+        if self.buf[..end].ends_with(b"\r") {
+            end -= 1;
+        }
+        end
+    }
+
+    //the line in buf as a Match
+    fn line(&mut self, byte_offset: usize) -> Match<'_> {
+        let end = self.line_end();
+        let line = match str::from_utf8(&self.buf[..end]) {
+            Ok(line) => line,
+            Err(_) => {
+                self.lossy = String::from_utf8_lossy(&self.buf[..end]).into_owned();
+                self.saw_invalid_utf8 = true;
+                &self.lossy
+            }
+        };
+
+        Match {
+            line_number: self.line_number,
+            byte_offset,
+            line,
+        }
+    }
+
+    /// Skips ahead to the next line that contains the finder's needle and
+    /// reads it. Instead of splitting everything into lines, the whole
+    /// buffer gets searched for the needle and only the newlines before a
+    /// hit are counted to keep the line numbers right. The needle mustn't
+    /// contain a newline. Every line it hands back has the needle in it.
+    pub fn next_line_containing(&mut self, finder: &Finder) -> io::Result<Option<Match<'_>>> {
+        loop {
+            let buf = self.reader.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(None);
+            }
+
+            //we're always at the start of a line here, so everything up to
+            //the last newline before the hit can go
+            let hit = finder.find(buf);
+            let skip = match hit {
+                Some(hit) => literal::memrchr(b'\n', &buf[..hit]).map_or(0, |nl| nl + 1),
+                //keep the last line, the needle could continue in the next read
+                None => literal::memrchr(b'\n', buf).map_or(0, |nl| nl + 1),
+            };
+            self.line_number += literal::count(b'\n', &buf[..skip]);
+            self.offset += skip;
+            self.reader.consume(skip);
+            if hit.is_none() && skip > 0 {
+                continue;
+            }
+
+            //a hit is on this line now, as long as it ends before the line
+            //does. no hit means the line is longer than the buffer, and it
+            //has to be read to know if the needle is further along in it
+            let Some(byte_offset) = self.read_line()? else {
+                return Ok(None);
+            };
+            let end = self.line_end();
+            let found = match hit {
+                Some(hit) => hit - skip + finder.needle_len() <= end,
+                None => finder.find(&self.buf[..end]).is_some(),
+            };
+            if found {
+                return Ok(Some(self.line(byte_offset)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn numbers_offsets_and_endings() {
+        let input: &[u8] = b"Rust:\r\nsafe\n\nPick three."; //&[u8] implements BufRead
+        let mut reader = LineReader::new(input);
+        let mut lines = Vec::new();
+        while let Some(m) = reader.next_line().unwrap() {
+            lines.push((m.line_number, m.byte_offset, m.line.to_string()));
+        }
+
+        assert_eq!(
+            vec![
+                (1, 0, "Rust:".to_string()),
+                (2, 7, "safe".to_string()),
+                (3, 12, "".to_string()),
+                (4, 13, "Pick three.".to_string()),
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn skips_to_the_needle() {
+        let input = "no\n".repeat(30_000) + "a needle\r\nno needle\rno\r\nneedle again";
+        //a tiny buffer, so hits and lines end up across reads
+        let mut reader = LineReader::new(io::BufReader::with_capacity(7, input.as_bytes()));
+        let finder = Finder::new(b"needle");
+        let mut lines = Vec::new();
+        while let Some(m) = reader.next_line_containing(&finder).unwrap() {
+            lines.push((m.line_number, m.byte_offset, m.line.to_string()));
+        }
+
+        assert_eq!(
+            vec![
+                (30_001, 90_000, "a needle".to_string()),
+                (30_002, 90_010, "no needle\rno".to_string()),
+                (30_003, 90_024, "needle again".to_string())
+            ],
+            lines
+        );
+        assert_eq!(input.len(), reader.bytes_read());
+
+        //"le\r" is in the buffer, but the \r is the line ending and not part of the line
+        let mut reader = LineReader::new(&b"needle\r\nneedle\rx\n"[..]);
+        let finder = Finder::new(b"le\r");
+        assert_eq!(2, reader.next_line_containing(&finder).unwrap().unwrap().line_number);
+        assert!(reader.next_line_containing(&finder).unwrap().is_none());
+    }
+
+    #[test]
+    fn invalid_utf8_is_lossy() {
+        let input: &[u8] = b"caf\xe9 au lait\nok\n";
+        let mut reader = LineReader::new(input);
+        assert_eq!("caf\u{fffd} au lait", reader.next_line().unwrap().unwrap().line);
+        assert!(reader.saw_invalid_utf8());
+        let second = reader.next_line().unwrap().unwrap();
+        assert_eq!(("ok", 13), (second.line, second.byte_offset));
+        assert!(reader.next_line().unwrap().is_none());
+    }
+
+    #[test]
+    fn binary_data() {
+        assert!(!is_binary(b"Rust:\nsafe, fast, productive.\n"));
+        assert!(!is_binary("caf\u{e9}".as_bytes()));
+        assert!(!is_binary(&"caf\u{e9}".as_bytes()[..4])); //cut off in the middle of the \u{e9}
+        assert!(is_binary(b"\x7fELF\x02\x01\x01\x00"));
+        assert!(is_binary(b"caf\xe9 au lait"));
+    }
+}
@@ -0,0 +1,536 @@
+use std::borrow::Cow;
+use std::io::{self, BufRead};
+
+use crate::reader::LineReader;
+
+//--csv, --tsv and --jsonl: files of records, where matching anywhere in a
+//line finds the query in the wrong column or in a key instead of a value.
+//a record is split up and only one field of it gets matched, and then the
+//whole record is what's selected. a csv record is usually a line, but a
+//quoted field can have newlines in it and then the record goes on over
+//several lines. a json lines record is always one line
+
+/// The part of every record that gets matched, for a Searcher whose
+/// input is records instead of lines of text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    kind: Kind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    Separated { name: String, delimiter: u8 }, //the column called name in the header
+    Json(Vec<Step>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+impl Field {
+    /// The column called `name` in the header of comma separated values.
+    pub fn csv(name: &str) -> Field {
+        Field {
+            kind: Kind::Separated {
+                name: name.to_string(),
+                delimiter: b',',
+            },
+        }
+    }
+
+    /// The column called `name` in the header of tab separated values.
+    pub fn tsv(name: &str) -> Field {
+        Field {
+            kind: Kind::Separated {
+                name: name.to_string(),
+                delimiter: b'\t',
+            },
+        }
+    }
+
+    /// The value at `path` in every line of JSON Lines, with keys after
+    /// dots and array indexes in brackets like jq: `.user.name`,
+    /// `.items[0].id`, or `.` for the whole line. None if that's not
+    /// what `path` looks like.
+    pub fn jsonl(path: &str) -> Option<Field> {
+        if path.is_empty() {
+            return None;
+        }
+        let mut steps = Vec::new();
+        let mut rest = if path == "." { "" } else { path };
+        while !rest.is_empty() {
+            if let Some(index) = rest.strip_prefix('[') {
+                let (digits, after) = index.split_once(']')?;
+                steps.push(Step::Index(digits.parse().ok()?));
+                rest = after;
+            } else {
+                let key = rest.strip_prefix('.')?;
+                let end = key.find(['.', '[']).unwrap_or(key.len());
+                if end == 0 {
+                    return None;
+                }
+                steps.push(Step::Key(key[..end].to_string()));
+                rest = &key[end..];
+            }
+        }
+        Some(Field { kind: Kind::Json(steps) })
+    }
+}
+
+/// A record, and its field if it has one.
+pub(crate) struct Record<'a> {
+    pub line_number: usize,
+    pub last_line: usize, //the line it ends on
+    pub byte_offset: usize,
+    pub text: &'a str, //the whole record, with newlines in it if it has more than one line
+    pub value: Option<Value<'a>>,
+}
+
+/// What the field of a record says, and where it is in the record.
+pub(crate) struct Value<'a> {
+    pub text: Cow<'a, str>, //without quotes or escapes
+    pub span: (usize, usize), //where it is in the record, inside the quotes if there are any
+}
+
+/// Reads records one at a time, like LineReader does lines.
+pub(crate) struct Records<R: BufRead> {
+    lines: LineReader<R>,
+    field: Field,
+    column: Option<usize>, //for csv and tsv, the column of the field, once the header has been read
+    text: String,
+    //where the record in text is
+    line_number: usize,
+    last_line: usize,
+    byte_offset: usize,
+}
+
+impl<R: BufRead> Records<R> {
+    pub fn new(reader: R, field: &Field) -> Records<R> {
+        Records {
+            lines: LineReader::new(reader),
+            field: field.clone(),
+            column: None,
+            text: String::new(),
+            line_number: 0,
+            last_line: 0,
+            byte_offset: 0,
+        }
+    }
+
+    pub fn bytes_read(&self) -> usize {
+        self.lines.bytes_read()
+    }
+
+    pub fn saw_invalid_utf8(&self) -> bool {
+        self.lines.saw_invalid_utf8()
+    }
+
+    /// The next record. For csv and tsv the header is read first, and not
+    /// having a field with the name in it is an error.
+    pub fn next_record(&mut self) -> io::Result<Option<Record<'_>>> {
+        let value = match &self.field.kind {
+            Kind::Separated { delimiter, .. } => {
+                let delimiter = *delimiter;
+                if self.column.is_none() && !self.header(delimiter)? {
+                    return Ok(None);
+                }
+                let Some(fields) = self.next_separated(delimiter)? else {
+                    return Ok(None);
+                };
+                let text = self.text.as_str();
+                self.column.and_then(|column| fields.get(column)).map(|&(start, end)| {
+                    let (value, inner) = unquote(&text[start..end]);
+                    Value {
+                        text: value,
+                        span: (start + inner.0, start + inner.1),
+                    }
+                })
+            }
+            Kind::Json(path) => {
+                //blank lines in between aren't records
+                loop {
+                    let Some(line) = self.lines.next_line()? else {
+                        return Ok(None);
+                    };
+                    if line.line.trim().is_empty() {
+                        continue;
+                    }
+                    (self.line_number, self.last_line, self.byte_offset) =
+                        (line.line_number, line.line_number, line.byte_offset);
+                    self.text.clear();
+                    self.text.push_str(line.line);
+                    break json_value(&self.text, path);
+                }
+            }
+        };
+        Ok(Some(Record {
+            line_number: self.line_number,
+            last_line: self.last_line,
+            byte_offset: self.byte_offset,
+            text: &self.text,
+            value,
+        }))
+    }
+
+    //reads the header and finds the field's column in it. false if there's no header at all
+    fn header(&mut self, delimiter: u8) -> io::Result<bool> {
+        let Some(fields) = self.next_separated(delimiter)? else {
+            return Ok(false);
+        };
+        let Kind::Separated { name, .. } = &self.field.kind else {
+            return Ok(false);
+        };
+        match fields.iter().position(|&(start, end)| unquote(&self.text[start..end]).0 == name.as_str()) {
+            Some(column) => self.column = Some(column),
+            None => {
+                let msg = format!("no field called {name:?} in the header");
+                return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+            }
+        }
+        Ok(true)
+    }
+
+    //reads lines into text until the quotes are closed, and splits it into
+    //the byte ranges of its fields (quotes and all)
+    fn next_separated(&mut self, delimiter: u8) -> io::Result<Option<Vec<(usize, usize)>>> {
+        let Some(line) = self.lines.next_line()? else {
+            return Ok(None);
+        };
+        (self.line_number, self.last_line, self.byte_offset) =
+            (line.line_number, line.line_number, line.byte_offset);
+        self.text.clear();
+        self.text.push_str(line.line);
+
+        let mut split = Split::new(delimiter);
+        split.push(&self.text);
+        while split.in_quotes {
+            //a quote that's never closed ends at the end of the file
+            let Some(line) = self.lines.next_line()? else {
+                break;
+            };
+            self.last_line = line.line_number;
+            self.text.push('\n');
+            self.text.push_str(line.line);
+            split.push(&self.text);
+        }
+        Ok(Some(split.finish(self.text.len())))
+    }
+}
+
+//splits a record into fields, as more of the record comes in. a field
+//that starts with a quote goes on to the next quote that isn't doubled,
+//delimiters and newlines included, anything else ends at a delimiter
+struct Split {
+    delimiter: u8,
+    fields: Vec<(usize, usize)>,
+    start: usize, //where the current field starts
+    pos: usize,   //how far the text has been looked at
+    in_quotes: bool,
+}
+
+impl Split {
+    fn new(delimiter: u8) -> Split {
+        Split {
+            delimiter,
+            fields: Vec::new(),
+            start: 0,
+            pos: 0,
+            in_quotes: false,
+        }
+    }
+
+    //`text` is everything so far, the part up to pos was already seen
+    fn push(&mut self, text: &str) {
+        let bytes = text.as_bytes();
+        while self.pos < bytes.len() {
+            let b = bytes[self.pos];
+            if self.in_quotes {
+                if b == b'"' {
+                    match bytes.get(self.pos + 1) {
+                        Some(b'"') => self.pos += 1,
+                        _ => self.in_quotes = false,
+                    }
+                }
+            } else if b == b'"' && self.pos == self.start {
+                self.in_quotes = true;
+            } else if b == self.delimiter {
+                self.fields.push((self.start, self.pos));
+                self.start = self.pos + 1;
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn finish(mut self, len: usize) -> Vec<(usize, usize)> {
+        self.fields.push((self.start, len));
+        self.fields
+    }
+}
+
+//a field without its quotes and with "" turned back into ", and the byte
+//range of what's inside the quotes
+fn unquote(field: &str) -> (Cow<'_, str>, (usize, usize)) {
+    let Some(inner) = field.strip_prefix('"') else {
+        return (Cow::Borrowed(field), (0, field.len()));
+    };
+    //whatever comes after the closing quote (which it shouldn't) is kept
+    let inner = inner.strip_suffix('"').unwrap_or(inner);
+    let span = (1, 1 + inner.len());
+    match inner.contains("\"\"") {
+        true => (Cow::Owned(inner.replace("\"\"", "\"")), span),
+        false => (Cow::Borrowed(inner), span),
+    }
+}
+
+//the value at `path` in a line of json. a string is matched without its
+//quotes and escapes, anything else (numbers, true, objects) as it's written.
+//a line that isn't json, or has nothing at the path, has no value
+fn json_value<'a>(line: &'a str, path: &[Step]) -> Option<Value<'a>> {
+    let bytes = line.as_bytes();
+    let mut pos = skip_space(bytes, 0);
+    for step in path {
+        pos = match (step, bytes.get(pos)?) {
+            (Step::Key(key), b'{') => member(line, pos, key)?,
+            (Step::Index(index), b'[') => element(bytes, pos, *index)?,
+            _ => return None,
+        };
+    }
+    let end = skip_value(bytes, pos)?;
+    //. is the whole line, which has to be nothing but the one value
+    if path.is_empty() && skip_space(bytes, end) != bytes.len() {
+        return None;
+    }
+    if bytes[pos] != b'"' {
+        return Some(Value {
+            text: Cow::Borrowed(&line[pos..end]),
+            span: (pos, end),
+        });
+    }
+    Some(Value {
+        text: json_string(&line[pos + 1..end - 1])?,
+        span: (pos + 1, end - 1),
+    })
+}
+
+//where the value of `key` starts, in the object at pos. the first one wins
+//if it's there more than once
+fn member(line: &str, pos: usize, key: &str) -> Option<usize> {
+    let bytes = line.as_bytes();
+    let mut pos = skip_space(bytes, pos + 1);
+    if bytes.get(pos) == Some(&b'}') {
+        return None;
+    }
+    loop {
+        if bytes.get(pos) != Some(&b'"') {
+            return None;
+        }
+        let end = skip_string(bytes, pos)?;
+        let name = json_string(&line[pos + 1..end - 1])?;
+        pos = skip_space(bytes, end);
+        if bytes.get(pos) != Some(&b':') {
+            return None;
+        }
+        pos = skip_space(bytes, pos + 1);
+        if name == key {
+            return Some(pos);
+        }
+        pos = skip_space(bytes, skip_value(bytes, pos)?);
+        match bytes.get(pos)? {
+            b',' => pos = skip_space(bytes, pos + 1),
+            _ => return None, //the end of the object, or not json
+        }
+    }
+}
+
+//where element `index` starts, in the array at pos
+fn element(bytes: &[u8], pos: usize, index: usize) -> Option<usize> {
+    let mut pos = skip_space(bytes, pos + 1);
+    if bytes.get(pos) == Some(&b']') {
+        return None;
+    }
+    for _ in 0..index {
+        pos = skip_space(bytes, skip_value(bytes, pos)?);
+        match bytes.get(pos)? {
+            b',' => pos = skip_space(bytes, pos + 1),
+            _ => return None,
+        }
+    }
+    Some(pos)
+}
+
+fn skip_space(bytes: &[u8], mut pos: usize) -> usize {
+    while bytes.get(pos).is_some_and(|b| matches!(b, b' ' | b'\t' | b'\r' | b'\n')) {
+        pos += 1;
+    }
+    pos
+}
+
+//where the string starting at pos ends, after its closing quote
+fn skip_string(bytes: &[u8], mut pos: usize) -> Option<usize> {
+    pos += 1;
+    loop {
+        match bytes.get(pos)? {
+            b'"' => return Some(pos + 1),
+            b'\\' => pos += 2,
+            _ => pos += 1,
+        }
+    }
+}
+
+//where the value starting at pos ends. objects and arrays are skipped by
+//counting brackets instead of recursing, so no line is nested too deep.
+//this only finds the end, it doesn't check that what's in between is json
+fn skip_value(bytes: &[u8], pos: usize) -> Option<usize> {
+    match bytes.get(pos)? {
+        b'"' => skip_string(bytes, pos),
+        b'{' | b'[' => {
+            let mut depth = 0;
+            let mut pos = pos;
+            loop {
+                match bytes.get(pos)? {
+                    b'"' => {
+                        pos = skip_string(bytes, pos)?;
+                        continue;
+                    }
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return Some(pos + 1);
+                        }
+                    }
+                    _ => {}
+                }
+                pos += 1;
+            }
+        }
+        //a number, true, false or null
+        _ => {
+            let len = bytes[pos..]
+                .iter()
+                .position(|b| matches!(b, b',' | b'}' | b']' | b' ' | b'\t' | b'\r' | b'\n'))
+                .unwrap_or(bytes.len() - pos);
+            (len > 0).then_some(pos + len)
+        }
+    }
+}
+
+//the text of a json string, from between its quotes
+fn json_string(raw: &str) -> Option<Cow<'_, str>> {
+    if !raw.contains('\\') {
+        return Some(Cow::Borrowed(raw));
+    }
+    let mut text = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        let c = match chars.next()? {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'u' => {
+                let unit = hex(&mut chars)?;
+                //outside the basic plane it's two escapes, a surrogate pair
+                match unit {
+                    0xd800..=0xdbff => {
+                        if (chars.next(), chars.next()) != (Some('\\'), Some('u')) {
+                            return None;
+                        }
+                        let low = hex(&mut chars)?.checked_sub(0xdc00)?;
+                        char::from_u32(0x10000 + ((unit - 0xd800) << 10) + low)?
+                    }
+                    _ => char::from_u32(unit).unwrap_or('\u{fffd}'),
+                }
+            }
+            c => c, //\" \\ and \/
+        };
+        text.push(c);
+    }
+    Some(Cow::Owned(text))
+}
+
+//the four hex digits of a \u escape
+fn hex(chars: &mut std::str::Chars) -> Option<u32> {
+    let digits: String = chars.take(4).collect();
+    match digits.len() {
+        4 => u32::from_str_radix(&digits, 16).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    //(line number, last line, the record, the value) of every record
+    fn records(field: &Field, text: &str) -> Vec<(usize, usize, String, Option<String>)> {
+        let mut records = Records::new(text.as_bytes(), field);
+        let mut all = Vec::new();
+        while let Some(record) = records.next_record().unwrap() {
+            let value = record.value.map(|value| value.text.into_owned());
+            all.push((record.line_number, record.last_line, record.text.to_string(), value));
+        }
+        all
+    }
+
+    //the text where the record says its value is
+    fn span(field: &Field, text: &str) -> String {
+        let mut records = Records::new(text.as_bytes(), field);
+        let record = records.next_record().unwrap().unwrap();
+        let (start, end) = record.value.unwrap().span;
+        record.text[start..end].to_string()
+    }
+
+    #[test]
+    fn csv_fields() {
+        let csv = "id,\"the name\",note\r\n1,Ann,\"says \"\"hi\"\"\"\n2,\"Bob, Jr.\",\"two\nlines\"\n3\n";
+        let name = records(&Field::csv("the name"), csv);
+        let value = |i: usize| name[i].3.as_deref();
+        assert_eq!((Some("Ann"), Some("Bob, Jr."), None), (value(0), value(1), value(2)));
+        let note = records(&Field::csv("note"), csv);
+        assert_eq!((3, 4, "2,\"Bob, Jr.\",\"two\nlines\""), (note[1].0, note[1].1, note[1].2.as_str()));
+        assert_eq!((Some("says \"hi\""), Some("two\nlines")), (note[0].3.as_deref(), note[1].3.as_deref()));
+        assert_eq!(5, note[2].0);
+
+        assert_eq!("says \"\"hi\"\"", span(&Field::csv("note"), csv));
+
+        let tsv = records(&Field::tsv("b"), "a\tb\n1\tx,y\n");
+        assert_eq!(Some("x,y"), tsv[0].3.as_deref());
+
+        let mut missing = Records::new(csv.as_bytes(), &Field::csv("name"));
+        assert_eq!(io::ErrorKind::InvalidData, missing.next_record().err().unwrap().kind());
+    }
+
+    #[test]
+    fn json_paths() {
+        let lines = concat!(
+            r#"{"user": {"name": "Ann \"A\" é😀", "tags": ["x", {"id": 7}]}, "n": 1}"#,
+            "\n\n",
+            r#"{"user": {"tags": "]}"}, "user": 2}"#,
+            "\nnot json\n",
+        );
+        let value = |path: &str| -> Vec<Option<String>> {
+            let field = Field::jsonl(path).unwrap();
+            records(&field, lines).into_iter().map(|record| record.3).collect()
+        };
+        assert_eq!(vec![Some("Ann \"A\" é😀".to_string()), None, None], value(".user.name"));
+        assert_eq!(vec![Some("7".to_string()), None, None], value(".user.tags[1].id"));
+        assert_eq!(vec![Some("1".to_string()), None, None], value(".n"));
+        assert_eq!(Some("]}".to_string()), value(".user.tags")[1]);
+        assert_eq!(None, value(".")[2]);
+        assert_eq!(r#"Ann \"A\" é😀"#, span(&Field::jsonl(".user.name").unwrap(), lines));
+        assert_eq!(vec![1, 3, 4], records(&Field::jsonl(".n").unwrap(), lines).iter().map(|r| r.0).collect::<Vec<_>>());
+
+        assert!(Field::jsonl(".items[0].id").is_some() && Field::jsonl("[2]").is_some());
+        for bad in ["", "user", ".user.", "..user", ".items[x]", ".a[0]."] {
+            assert_eq!(None, Field::jsonl(bad), "{bad}");
+        }
+    }
+}
@@ -0,0 +1,1359 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::aho_corasick::AhoCorasick;
+use crate::casefold;
+use crate::fuzzy::{Fuzzy, FuzzyMatch};
+use crate::literal::Finder;
+use crate::matcher::{FindIter, Matcher};
+
+//a small regular expression engine so minigrep doesn't need any
+//external crates. the pattern is parsed into a tree (Node), the tree is
+//compiled into a list of instructions (Inst), and the instructions are
+//run by a "pike vm", which steps every possible match attempt forward
+//one character at a time. that means the running time is linear in the
+//length of the line no matter how nasty the pattern is (no exponential
+//backtracking like a naive recursive matcher would have)
+//
+//several patterns can be built into one Regex (build_many), each one its
+//own branch ending in its own Match, so a match knows which pattern it
+//came from. if every pattern is plain text the vm isn't used at all and
+//an aho-corasick automaton finds them instead. Regex::fuzzy makes one that
+//leaves everything to a Fuzzy, for --fuzzy
+//
+//supported syntax:
+//  literals, escapes (\. \\ \t \n \r and friends)
+//  .              any char except newline (any char at all with (?s))
+//  [abc] [^a-z]   character classes, with \d \w \s allowed inside
+//  \d \w \s       and their negations \D \W \S
+//  ^ $            start/end of the text (of a line with (?m))
+//  \b \B          word boundary / not a word boundary
+//  \b{start-half} \b{end-half}   not preceded / not followed by a word char
+//  a|b            alternation
+//  * + ? {n} {n,} {n,m}   repetition, add a trailing ? to make it lazy
+//  (...)          capturing group
+//  (?:...)        non-capturing group
+//  (?i) (?m) (?s) inline flags, also scoped as (?i:...)
+
+const MAX_REPEAT: u32 = 1000;
+const MAX_PROGRAM: usize = 100_000; //keeps {1000}{1000} style patterns from eating all memory
+const MAX_NESTING: usize = 250; //groups and repetitions inside each other, parsing and compiling them recurses
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegexError {
+    pub pattern: Option<usize>, //which pattern of build_many, None if it's all of them together
+    pub pos: usize,     //char index in the pattern where things went wrong
+    pub msg: &'static str,
+}
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid regex: {} at position {}", self.msg, self.pos)
+    }
+}
+
+impl Error for RegexError {} //Display + Debug is all Error needs, so we can use ? into Box<dyn Error>
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ClassItem {
+    Range(char, char),
+    Digit(bool), //the bool is true for the negated version (\D)
+    Word(bool),
+    Space(bool),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Class {
+    items: Vec<ClassItem>,
+    negated: bool,
+    ignore_case: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Look {
+    Start { multi_line: bool },
+    End { multi_line: bool },
+    WordBoundary,
+    NotWordBoundary,
+    WordStartHalf, //no word char before, what grep -w wants in front of a match
+    WordEndHalf,   //no word char after
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Empty,
+    Literal(char, bool), //bool is ignore_case
+    Any(bool),           //bool is dot_matches_new_line
+    Class(Class),
+    Look(Look),
+    Group(Box<Node>, Option<usize>), //None for (?:...)
+    Concat(Vec<Node>),
+    Alt(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+        greedy: bool,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Inst {
+    Char(char, bool), //with ignore_case the char is stored already folded
+    Any(bool),
+    Class(Class),
+    Assert(Look),
+    Save(usize),
+    Split(usize, usize), //try the first target before the second
+    Jmp(usize),
+    Match(usize), //the index of the pattern that matched
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Flags {
+    ignore_case: bool,
+    multi_line: bool,
+    dot_all: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Regex {
+    prog: Vec<Inst>,
+    slots: usize, //two slots (start, end) per group, group 0 is the whole match
+    literals: Option<Literals>, //set when every pattern is plain text, then prog is unused
+    fuzzy: Option<Fuzzy>,       //set by Regex::fuzzy, then it does all the matching
+    required: Required,
+}
+
+/// Text that has to be somewhere in anything a regex matches, for the
+/// trigram index to rule out files that can't have a match.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Required {
+    Anything, //nothing useful can be said
+    Text(String, bool), //the bool is ignore_case
+    All(Vec<Required>),
+    Any(Vec<Required>),
+}
+
+impl Required {
+    fn from_node(node: &Node) -> Required {
+        match node {
+            Node::Literal(c, ignore_case) => Required::Text(c.to_string(), *ignore_case),
+            Node::Group(inner, _) => Required::from_node(inner),
+            Node::Repeat { node, min, .. } if *min > 0 => Required::from_node(node),
+            Node::Alt(nodes) => Required::any(nodes.iter().map(Required::from_node).collect()),
+            Node::Concat(nodes) => {
+                //literals next to each other are one longer text, which says a lot
+                //more than the chars one by one. assertions take up no room, so
+                //the text goes on past them
+                let mut all = Vec::new();
+                let mut text = String::new();
+                let mut text_ignore_case = false;
+                for node in nodes {
+                    match node {
+                        Node::Look(_) | Node::Empty => {}
+                        Node::Literal(c, ignore_case) => {
+                            if *ignore_case != text_ignore_case && !text.is_empty() {
+                                all.push(Required::Text(std::mem::take(&mut text), text_ignore_case));
+                            }
+                            text.push(*c);
+                            text_ignore_case = *ignore_case;
+                        }
+                        _ => {
+                            if !text.is_empty() {
+                                all.push(Required::Text(std::mem::take(&mut text), text_ignore_case));
+                            }
+                            all.push(Required::from_node(node));
+                        }
+                    }
+                }
+                if !text.is_empty() {
+                    all.push(Required::Text(text, text_ignore_case));
+                }
+                Required::all(all)
+            }
+            _ => Required::Anything,
+        }
+    }
+
+    fn all(mut all: Vec<Required>) -> Required {
+        all.retain(|required| *required != Required::Anything);
+        match all.len() {
+            0 => Required::Anything,
+            1 => all.pop().unwrap(),
+            _ => Required::All(all),
+        }
+    }
+
+    fn any(mut any: Vec<Required>) -> Required {
+        if any.is_empty() || any.contains(&Required::Anything) {
+            return Required::Anything;
+        }
+        match any.len() {
+            1 => any.pop().unwrap(),
+            _ => Required::Any(any),
+        }
+    }
+}
+
+//plain text patterns, maybe with assertions around them like the ones -w
+//and -x add. the automaton finds the text and the assertions get checked
+//at both ends of every hit
+#[derive(Debug, Clone)]
+struct Literals {
+    ac: AhoCorasick,
+    looks: Vec<(Vec<Look>, Vec<Look>)>, //per pattern, the ones before and the ones after the text
+    single: Option<(String, Finder)>, //the text, if it's one case sensitive pattern without assertions
+}
+
+impl Regex {
+    //build and not new bc a bad pattern is an error the user can make
+    pub fn build(pattern: &str, ignore_case: bool) -> Result<Regex, RegexError> {
+        Regex::build_many(&[pattern], ignore_case)
+    }
+
+    /// Builds a regex that matches any of `patterns`, like joining them with |
+    /// but remembering which one matched (see find_pattern_at). When two of
+    /// them match at the same place the first one in the list wins.
+    pub fn build_many<S: AsRef<str>>(patterns: &[S], ignore_case: bool) -> Result<Regex, RegexError> {
+        let flags = Flags {
+            ignore_case,
+            multi_line: false,
+            dot_all: false,
+        };
+        let mut parser = Parser {
+            chars: Vec::new(),
+            pos: 0,
+            groups: 0, //shared, so the group numbers keep counting up through all the patterns
+            depth: 0,
+            height: 0,
+        };
+        let mut nodes = Vec::with_capacity(patterns.len());
+        for (i, pattern) in patterns.iter().enumerate() {
+            parser.chars = pattern.as_ref().chars().collect();
+            parser.pos = 0;
+            let node = parser.parse(flags).map_err(|err| RegexError { pattern: Some(i), ..err })?;
+            nodes.push(node);
+        }
+        let required = Required::any(nodes.iter().map(Required::from_node).collect());
+
+        if let Some(literals) = Literals::build(&nodes) {
+            return Ok(Regex {
+                prog: Vec::new(),
+                slots: 2,
+                literals: Some(literals),
+                fuzzy: None,
+                required,
+            });
+        }
+
+        //Save(0), then every pattern as a branch of a chain of splits:
+        //  split L1, L2;  L1: <pattern 0> save(1) match(0);  L2: split ...
+        let mut compiler = Compiler { prog: Vec::new() };
+        compiler.push(Inst::Save(0))?;
+        for (i, node) in nodes.iter().enumerate() {
+            let split = match i + 1 < nodes.len() {
+                true => Some(compiler.push(Inst::Split(0, 0))?),
+                false => None,
+            };
+            compiler.compile(node)?;
+            compiler.push(Inst::Save(1))?;
+            compiler.push(Inst::Match(i))?;
+            if let Some(split) = split {
+                let next = compiler.prog.len();
+                compiler.patch(split, Inst::Split(split + 1, next));
+            }
+        }
+
+        Ok(Regex {
+            prog: compiler.prog,
+            slots: 2 * (parser.groups + 1),
+            literals: None,
+            fuzzy: None,
+            required,
+        })
+    }
+
+    /// A regex that matches text within `max_edits` edits of one of the
+    /// `queries` (see Fuzzy), which are plain text and not patterns. It
+    /// goes everywhere a regex does, so that's how --fuzzy searches.
+    pub fn fuzzy<S: AsRef<str>>(queries: &[S], max_edits: usize, ignore_case: bool) -> Regex {
+        //cut a query into one piece more than there are edits, and at least
+        //one of them is untouched in every match
+        let pieces = |query: &str| -> Vec<Required> {
+            let chars: Vec<char> = query.chars().collect();
+            if chars.len() <= max_edits {
+                return vec![Required::Anything];
+            }
+            let size = chars.len() / (max_edits + 1);
+            let piece = |i: usize| {
+                let end = if i == max_edits { chars.len() } else { (i + 1) * size };
+                Required::Text(chars[i * size..end].iter().collect(), ignore_case)
+            };
+            (0..=max_edits).map(piece).collect()
+        };
+        Regex {
+            prog: Vec::new(),
+            slots: 2,
+            literals: None,
+            fuzzy: Some(Fuzzy::many(queries, max_edits, ignore_case)),
+            required: Required::any(queries.iter().flat_map(|query| pieces(query.as_ref())).collect()),
+        }
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        match &self.fuzzy {
+            Some(fuzzy) => fuzzy.is_match(text),
+            None => self.find_at(text, 0).is_some(),
+        }
+    }
+
+    /// Returns the byte range of the leftmost match starting the search at `start`.
+    pub fn find_at(&self, text: &str, start: usize) -> Option<(usize, usize)> {
+        self.find_pattern_at(text, start).map(|(start, end, _)| (start, end))
+    }
+
+    /// Like find_at, plus the index of the pattern (in the list given to
+    /// build_many) that matched.
+    pub fn find_pattern_at(&self, text: &str, start: usize) -> Option<(usize, usize, usize)> {
+        if let Some(literals) = &self.literals {
+            return literals.find_at(text, start);
+        }
+        if let Some(fuzzy) = &self.fuzzy {
+            return fuzzy.leftmost_at(text, start).map(|m| (m.start, m.end, m.pattern));
+        }
+        let (slots, pattern) = self.run(text, start)?;
+        Some((slots[0]?, slots[1]?, pattern))
+    }
+
+    /// Every non-overlapping match in `text`, left to right.
+    pub fn find_iter<'r, 't>(&'r self, text: &'t str) -> FindIter<'r, 't, Regex> {
+        FindIter::new(self, text)
+    }
+
+    /// The text this regex matches, if it's nothing but one piece of plain
+    /// text that can be on a line. Such a regex matches a line exactly when
+    /// the line contains the text, so searching can skip straight to where
+    /// it occurs.
+    pub fn literal(&self) -> Option<&str> {
+        let (text, _) = self.literals.as_ref()?.single.as_ref()?;
+        (!text.contains('\n')).then_some(text)
+    }
+
+    /// The closest match in `text` at or after `start` and how many edits
+    /// it is from its query, for a regex made by Regex::fuzzy.
+    pub fn best_fuzzy_at(&self, text: &str, start: usize) -> Option<FuzzyMatch> {
+        self.fuzzy.as_ref()?.best_at(text, start)
+    }
+
+    /// What every match has in it, see Required.
+    pub fn required(&self) -> &Required {
+        &self.required
+    }
+
+    //number of groups including the implicit group 0
+    pub fn captures_len(&self) -> usize {
+        self.slots / 2
+    }
+
+    /// Runs the pike vm and returns the start/end byte offset of every group
+    /// (group 0 is the whole match). Groups that didn't take part are None.
+    pub fn captures_at(&self, text: &str, start: usize) -> Option<Vec<Option<usize>>> {
+        if self.literals.is_some() || self.fuzzy.is_some() {
+            let (start, end, _) = self.find_pattern_at(text, start)?;
+            return Some(vec![Some(start), Some(end)]);
+        }
+        self.run(text, start).map(|(slots, _)| slots)
+    }
+
+    /// Replaces every match in `text` with `replacement`, in which $1 or ${1}
+    /// stands for what group 1 matched, $0 for the whole match and $$ for a
+    /// plain $. Also returns where each replacement ended up in the new text.
+    pub fn replace_all(&self, text: &str, replacement: &str) -> (String, Vec<(usize, usize)>) {
+        let matches: Vec<(usize, usize)> = self.find_iter(text).collect();
+        self.replace_matches(text, &matches, replacement)
+    }
+
+    /// Like replace_all, for matches of this regex that were already found.
+    /// `matches` are their byte ranges in `text`, left to right.
+    pub fn replace_matches(&self, text: &str, matches: &[(usize, usize)], replacement: &str) -> (String, Vec<(usize, usize)>) {
+        let mut replaced = String::with_capacity(text.len());
+        let mut spans = Vec::new();
+        let mut copied = 0;
+
+        for &(start, end) in matches {
+            replaced.push_str(&text[copied..start]);
+            let at = replaced.len();
+            if replacement.contains('$') {
+                //find_iter only has the whole match, so look again for the groups
+                let slots = self.captures_at(text, start).unwrap_or_default();
+                expand(replacement, text, &slots, &mut replaced);
+            } else {
+                replaced.push_str(replacement);
+            }
+            spans.push((at, replaced.len()));
+            copied = end;
+        }
+        replaced.push_str(&text[copied..]);
+        (replaced, spans)
+    }
+
+    //the vm itself, returns the slots and the index of the pattern that matched
+    fn run(&self, text: &str, start: usize) -> Option<(Vec<Option<usize>>, usize)> {
+        let mut clist = ThreadList::new(self.prog.len());
+        let mut nlist = ThreadList::new(self.prog.len());
+        let mut matched: Option<(Vec<Option<usize>>, usize)> = None;
+        let mut pos = start;
+        let empty = vec![None; self.slots];
+
+        loop {
+            //a new attempt starting here has lower priority than every attempt
+            //that started earlier, so it goes at the end of the list
+            if matched.is_none() {
+                self.add_thread(&mut clist, 0, pos, &empty, text);
+            }
+            if clist.is_empty() && matched.is_some() {
+                break;
+            }
+
+            let next = text[pos..].chars().next();
+            for i in 0..clist.threads.len() {
+                let (pc, ref slots) = clist.threads[i];
+                let step = match (&self.prog[pc], next) {
+                    (Inst::Match(pattern), _) => {
+                        matched = Some((slots.clone(), *pattern));
+                        break; //everything after this thread has lower priority
+                    }
+                    (Inst::Char(x, ci), Some(c)) => *x == c || (*ci && *x == casefold::fold(c)),
+                    (Inst::Any(dot_all), Some(c)) => *dot_all || c != '\n',
+                    (Inst::Class(class), Some(c)) => class.matches(c),
+                    _ => false,
+                };
+                if let (true, Some(c)) = (step, next) {
+                    let slots = slots.clone();
+                    self.add_thread(&mut nlist, pc + 1, pos + c.len_utf8(), &slots, text);
+                }
+            }
+
+            match next {
+                Some(c) => pos += c.len_utf8(),
+                None => break,
+            }
+            std::mem::swap(&mut clist, &mut nlist);
+            nlist.clear();
+        }
+
+        matched
+    }
+
+    //follows jumps, splits, saves and assertions (the instructions that don't
+    //consume a char) and adds every thread it reaches to the list in priority order
+    fn add_thread(&self, list: &mut ThreadList, pc: usize, pos: usize, slots: &[Option<usize>], text: &str) {
+        let mut stack: Vec<(usize, Vec<Option<usize>>)> = vec![(pc, slots.to_vec())];
+
+        while let Some((pc, mut slots)) = stack.pop() {
+            if list.seen[pc] {
+                continue;
+            }
+            list.seen[pc] = true;
+
+            match &self.prog[pc] {
+                Inst::Jmp(target) => stack.push((*target, slots)),
+                Inst::Split(first, second) => {
+                    //pushed in reverse so the first target is explored first
+                    stack.push((*second, slots.clone()));
+                    stack.push((*first, slots));
+                }
+                Inst::Save(slot) => {
+                    slots[*slot] = Some(pos);
+                    stack.push((pc + 1, slots));
+                }
+                Inst::Assert(look) => {
+                    if look.holds(text, pos) {
+                        stack.push((pc + 1, slots));
+                    }
+                }
+                _ => list.threads.push((pc, slots)),
+            }
+        }
+    }
+}
+
+//writes replacement to out with the $ references filled in from slots.
+//a group that doesn't exist or didn't take part is empty, and a $ that
+//isn't followed by a number, {number} or another $ is just a $
+fn expand(replacement: &str, text: &str, slots: &[Option<usize>], out: &mut String) {
+    let mut rest = replacement;
+    while let Some(dollar) = rest.find('$') {
+        out.push_str(&rest[..dollar]);
+        rest = &rest[dollar + 1..];
+
+        let (digits, after) = if let Some(braced) = rest.strip_prefix('{') {
+            match braced.find('}') {
+                Some(close) if braced[..close].chars().all(|c| c.is_ascii_digit()) && close > 0 => {
+                    (&braced[..close], &braced[close + 1..])
+                }
+                _ => ("", rest),
+            }
+        } else {
+            let len = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+            (&rest[..len], &rest[len..])
+        };
+
+        if digits.is_empty() {
+            out.push('$');
+            if let Some(after) = rest.strip_prefix('$') {
+                rest = after; //$$ is one $
+            }
+            continue;
+        }
+        if let Ok(group) = digits.parse::<usize>() {
+            if let (Some(Some(start)), Some(Some(end))) = (slots.get(2 * group), slots.get(2 * group + 1)) {
+                out.push_str(&text[*start..*end]);
+            }
+        }
+        rest = after;
+    }
+    out.push_str(rest);
+}
+
+struct ThreadList {
+    threads: Vec<(usize, Vec<Option<usize>>)>,
+    seen: Vec<bool>,
+}
+
+impl ThreadList {
+    fn new(size: usize) -> ThreadList {
+        ThreadList {
+            threads: Vec::new(),
+            seen: vec![false; size],
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.threads.is_empty()
+    }
+
+    fn clear(&mut self) {
+        self.threads.clear();
+        for seen in self.seen.iter_mut() {
+            *seen = false;
+        }
+    }
+}
+
+pub(crate) fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Escapes every special char in `text`, so the pattern matches it literally (-F).
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if r"\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+impl Literals {
+    fn build(nodes: &[Node]) -> Option<Literals> {
+        let mut texts = Vec::with_capacity(nodes.len());
+        let mut looks = Vec::with_capacity(nodes.len());
+        let mut case: Option<bool> = None; //the automaton is either all ignore_case or not at all
+
+        for node in nodes {
+            let mut items = Vec::new();
+            if !flatten(node, &mut items) {
+                return None;
+            }
+            //<looks> <at least one literal> <looks>, and nothing else
+            let first = items.iter().position(|item| matches!(item, Node::Literal(..)))?;
+            let last = items.iter().rposition(|item| matches!(item, Node::Literal(..)))?;
+            let mut text = Vec::with_capacity(last + 1 - first);
+            for item in &items[first..=last] {
+                match item {
+                    Node::Literal(c, ci) if case.is_none_or(|case| case == *ci) => {
+                        case = Some(*ci);
+                        text.push(*c);
+                    }
+                    _ => return None,
+                }
+            }
+            texts.push(text);
+            looks.push((only_looks(&items[..first]), only_looks(&items[last + 1..])));
+        }
+
+        let single = match (texts.as_slice(), &looks[..]) {
+            ([text], [(before, after)]) if case == Some(false) && before.is_empty() && after.is_empty() => {
+                let text: String = text.iter().collect();
+                let finder = Finder::new(text.as_bytes());
+                Some((text, finder))
+            }
+            _ => None,
+        };
+        Some(Literals {
+            ac: AhoCorasick::new(&texts, case.unwrap_or(false)),
+            looks,
+            single,
+        })
+    }
+
+    fn find_at(&self, text: &str, start: usize) -> Option<(usize, usize, usize)> {
+        //one piece of text doesn't need the automaton, the finder is a lot faster
+        if let Some((single, finder)) = &self.single {
+            let at = start + finder.find(&text.as_bytes()[start..])?;
+            return Some((at, at + single.len(), 0));
+        }
+        self.ac.find_at(text, start, |begin, end, pattern| {
+            let (before, after) = &self.looks[pattern];
+            before.iter().all(|look| look.holds(text, begin)) && after.iter().all(|look| look.holds(text, end))
+        })
+    }
+}
+
+fn only_looks(items: &[&Node]) -> Vec<Look> {
+    items
+        .iter()
+        .filter_map(|item| match item {
+            Node::Look(look) => Some(*look),
+            _ => None,
+        })
+        .collect()
+}
+
+//a concatenation of literals and assertions, with non-capturing groups
+//flattened out, in the order they appear. false if there's anything else
+fn flatten<'n>(node: &'n Node, items: &mut Vec<&'n Node>) -> bool {
+    match node {
+        Node::Empty => true,
+        Node::Literal(..) | Node::Look(_) => {
+            items.push(node);
+            true
+        }
+        Node::Group(inner, None) => flatten(inner, items),
+        Node::Concat(nodes) => nodes.iter().all(|node| flatten(node, items)),
+        _ => false,
+    }
+}
+
+impl Look {
+    fn holds(&self, text: &str, pos: usize) -> bool {
+        let before = text[..pos].chars().next_back();
+        let after = text[pos..].chars().next();
+
+        match self {
+            Look::Start { multi_line } => before.is_none() || (*multi_line && before == Some('\n')),
+            Look::End { multi_line } => after.is_none() || (*multi_line && after == Some('\n')),
+            Look::WordBoundary | Look::NotWordBoundary => {
+                let at_boundary = before.is_some_and(is_word_char) != after.is_some_and(is_word_char);
+                at_boundary == (*self == Look::WordBoundary)
+            }
+            Look::WordStartHalf => !before.is_some_and(is_word_char),
+            Look::WordEndHalf => !after.is_some_and(is_word_char),
+        }
+    }
+}
+
+impl ClassItem {
+    fn matches(&self, c: char, ignore_case: bool) -> bool {
+        match *self {
+            ClassItem::Range(lo, hi) => {
+                if lo <= c && c <= hi {
+                    return true;
+                }
+                if !ignore_case {
+                    return false;
+                }
+                //try the other cases of c, e.g. 'Q' against [a-z] or 'ς' against [Σ]
+                casefold::variants(c).any(|v| lo <= v && v <= hi)
+            }
+            ClassItem::Digit(negated) => c.is_ascii_digit() != negated,
+            ClassItem::Word(negated) => is_word_char(c) != negated,
+            ClassItem::Space(negated) => c.is_whitespace() != negated,
+        }
+    }
+}
+
+impl Class {
+    fn matches(&self, c: char) -> bool {
+        let hit = self.items.iter().any(|item| item.matches(c, self.ignore_case));
+        hit != self.negated
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    groups: usize,
+    depth: usize,  //how many groups the parser is in
+    height: usize, //how many groups and repetitions deep the node it just parsed is
+}
+
+impl Parser {
+    fn error(&self, msg: &'static str) -> RegexError {
+        RegexError {
+            pattern: None,
+            pos: self.pos,
+            msg,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse(&mut self, flags: Flags) -> Result<Node, RegexError> {
+        let node = self.parse_alt(flags)?;
+        if self.pos < self.chars.len() {
+            //the only way parse_alt stops early is on a ')' without a '('
+            return Err(self.error("unmatched ')'"));
+        }
+        Ok(node)
+    }
+
+    fn parse_alt(&mut self, mut flags: Flags) -> Result<Node, RegexError> {
+        let mut branches = vec![self.parse_concat(&mut flags)?];
+        let mut height = self.height;
+        while self.eat('|') {
+            branches.push(self.parse_concat(&mut flags)?);
+            height = height.max(self.height);
+        }
+        self.height = height;
+
+        if branches.len() == 1 {
+            Ok(branches.pop().unwrap())
+        } else {
+            Ok(Node::Alt(branches))
+        }
+    }
+
+    //flags is &mut bc a bare (?i) changes the flags for the rest of the group,
+    //including later branches of an alternation
+    fn parse_concat(&mut self, flags: &mut Flags) -> Result<Node, RegexError> {
+        let mut items = Vec::new();
+        let mut height = 0;
+
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = match self.parse_atom(flags)? {
+                Some(atom) => atom,
+                None => continue, //it was a flag group like (?i)
+            };
+            let atom = self.parse_repeat(atom)?;
+            height = height.max(self.height);
+            items.push(atom);
+        }
+        self.height = height;
+
+        match items.len() {
+            0 => Ok(Node::Empty),
+            1 => Ok(items.pop().unwrap()),
+            _ => Ok(Node::Concat(items)),
+        }
+    }
+
+    fn parse_atom(&mut self, flags: &mut Flags) -> Result<Option<Node>, RegexError> {
+        let c = self.peek().unwrap();
+        self.pos += 1;
+        self.height = 0;
+
+        let node = match c {
+            '.' => Node::Any(flags.dot_all),
+            '^' => Node::Look(Look::Start { multi_line: flags.multi_line }),
+            '$' => Node::Look(Look::End { multi_line: flags.multi_line }),
+            '[' => Node::Class(self.parse_class(flags.ignore_case)?),
+            '(' => return self.parse_group(flags),
+            '\\' => self.parse_escape(flags.ignore_case)?,
+            '*' | '+' | '?' => return Err(self.error("repetition operator with nothing to repeat")),
+            _ => Node::Literal(c, flags.ignore_case),
+        };
+        Ok(Some(node))
+    }
+
+    fn parse_group(&mut self, flags: &mut Flags) -> Result<Option<Node>, RegexError> {
+        //each group is a few stack frames deep, and the stack isn't endless
+        if self.depth == MAX_NESTING {
+            return Err(self.error("nested too deep"));
+        }
+        self.depth += 1;
+        let group = self.parse_group_body(flags);
+        self.depth -= 1;
+        self.height += 1;
+        if self.height > MAX_NESTING {
+            return Err(self.error("nested too deep"));
+        }
+        group
+    }
+
+    fn parse_group_body(&mut self, flags: &mut Flags) -> Result<Option<Node>, RegexError> {
+        let mut index = None;
+
+        if self.eat('?') {
+            //either (?:...), (?flags) or (?flags:...)
+            let mut inner = *flags;
+            loop {
+                match self.peek() {
+                    Some('i') => inner.ignore_case = true,
+                    Some('m') => inner.multi_line = true,
+                    Some('s') => inner.dot_all = true,
+                    Some(':') => {
+                        self.pos += 1;
+                        break;
+                    }
+                    Some(')') => {
+                        self.pos += 1;
+                        *flags = inner;
+                        return Ok(None);
+                    }
+                    _ => return Err(self.error("unknown group flag")),
+                }
+                self.pos += 1;
+            }
+            let node = self.parse_alt(inner)?;
+            if !self.eat(')') {
+                return Err(self.error("unclosed group"));
+            }
+            return Ok(Some(Node::Group(Box::new(node), index)));
+        }
+
+        self.groups += 1;
+        index = Some(self.groups);
+        let node = self.parse_alt(*flags)?;
+        if !self.eat(')') {
+            return Err(self.error("unclosed group"));
+        }
+        Ok(Some(Node::Group(Box::new(node), index)))
+    }
+
+    fn parse_escape(&mut self, ignore_case: bool) -> Result<Node, RegexError> {
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return Err(self.error("trailing backslash")),
+        };
+        self.pos += 1;
+
+        let node = match c {
+            'b' if self.peek() == Some('{') => Node::Look(self.parse_boundary_name()?),
+            'b' => Node::Look(Look::WordBoundary),
+            'B' => Node::Look(Look::NotWordBoundary),
+            'A' => Node::Look(Look::Start { multi_line: false }),
+            'z' => Node::Look(Look::End { multi_line: false }),
+            _ => match self.class_escape(c)? {
+                Some(item) => Node::Class(Class {
+                    items: vec![item],
+                    negated: false,
+                    ignore_case: false,
+                }),
+                None => Node::Literal(self.literal_escape(c)?, ignore_case),
+            },
+        };
+        Ok(node)
+    }
+
+    //the {...} of \b{start-half} and \b{end-half}, same names as the regex crate uses
+    fn parse_boundary_name(&mut self) -> Result<Look, RegexError> {
+        let start = self.pos;
+        let end = match self.chars[start..].iter().position(|&c| c == '}') {
+            Some(len) => start + len,
+            None => return Err(self.error("unclosed word boundary name")),
+        };
+        let name: String = self.chars[start + 1..end].iter().collect();
+        let look = match name.as_str() {
+            "start-half" => Look::WordStartHalf,
+            "end-half" => Look::WordEndHalf,
+            _ => return Err(self.error("unknown word boundary name")),
+        };
+        self.pos = end + 1;
+        Ok(look)
+    }
+
+    fn class_escape(&self, c: char) -> Result<Option<ClassItem>, RegexError> {
+        let item = match c {
+            'd' => ClassItem::Digit(false),
+            'D' => ClassItem::Digit(true),
+            'w' => ClassItem::Word(false),
+            'W' => ClassItem::Word(true),
+            's' => ClassItem::Space(false),
+            'S' => ClassItem::Space(true),
+            _ => return Ok(None),
+        };
+        Ok(Some(item))
+    }
+
+    fn literal_escape(&self, c: char) -> Result<char, RegexError> {
+        match c {
+            't' => Ok('\t'),
+            'n' => Ok('\n'),
+            'r' => Ok('\r'),
+            '0' => Ok('\0'),
+            //escaping punctuation is always allowed, letters and digits are reserved
+            c if !c.is_alphanumeric() => Ok(c),
+            _ => Err(self.error("unknown escape sequence")),
+        }
+    }
+
+    fn parse_class(&mut self, ignore_case: bool) -> Result<Class, RegexError> {
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+        let mut first = true;
+
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Err(self.error("unclosed character class")),
+            };
+            self.pos += 1;
+
+            //a ']' right after the '[' (or '[^') is a literal
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+
+            let lo = if c == '\\' {
+                let e = match self.peek() {
+                    Some(e) => e,
+                    None => return Err(self.error("trailing backslash")),
+                };
+                self.pos += 1;
+                if let Some(item) = self.class_escape(e)? {
+                    items.push(item);
+                    continue;
+                }
+                self.literal_escape(e)?
+            } else {
+                c
+            };
+
+            //a '-' makes a range unless it is the last thing in the class
+            if self.peek() == Some('-') && self.chars.get(self.pos + 1).is_some_and(|&n| n != ']') {
+                self.pos += 1;
+                let mut hi = self.chars[self.pos];
+                self.pos += 1;
+                if hi == '\\' {
+                    let e = match self.peek() {
+                        Some(e) => e,
+                        None => return Err(self.error("trailing backslash")),
+                    };
+                    self.pos += 1;
+                    hi = self.literal_escape(e)?;
+                }
+                if hi < lo {
+                    return Err(self.error("invalid range in character class"));
+                }
+                items.push(ClassItem::Range(lo, hi));
+            } else {
+                items.push(ClassItem::Range(lo, lo));
+            }
+        }
+
+        Ok(Class {
+            items,
+            negated,
+            ignore_case,
+        })
+    }
+
+    fn parse_repeat(&mut self, mut atom: Node) -> Result<Node, RegexError> {
+        loop {
+            let start = self.pos;
+            let (min, max) = match self.peek() {
+                Some('*') => {
+                    self.pos += 1;
+                    (0, None)
+                }
+                Some('+') => {
+                    self.pos += 1;
+                    (1, None)
+                }
+                Some('?') => {
+                    self.pos += 1;
+                    (0, Some(1))
+                }
+                Some('{') => match self.parse_counts()? {
+                    Some(counts) => counts,
+                    None => {
+                        //not a valid {n,m}, so treat the '{' as a literal like grep does
+                        self.pos = start;
+                        return Ok(atom);
+                    }
+                },
+                _ => return Ok(atom),
+            };
+
+            if let Node::Look(_) = atom {
+                return Err(self.error("repetition operator on an anchor"));
+            }
+            //a repetition of a repetition is one more level, like a group
+            self.height += 1;
+            if self.height > MAX_NESTING {
+                return Err(self.error("nested too deep"));
+            }
+            let greedy = !self.eat('?');
+            atom = Node::Repeat {
+                node: Box::new(atom),
+                min,
+                max,
+                greedy,
+            };
+        }
+    }
+
+    //parses {n}, {n,} or {n,m}; returns None if the braces don't hold counts
+    fn parse_counts(&mut self) -> Result<Option<(u32, Option<u32>)>, RegexError> {
+        self.pos += 1; //the '{'
+        let min = match self.parse_number()? {
+            Some(min) => min,
+            None => return Ok(None),
+        };
+
+        let max = if self.eat(',') {
+            self.parse_number()?
+        } else {
+            Some(min)
+        };
+
+        if !self.eat('}') {
+            return Ok(None);
+        }
+        if max.is_some_and(|max| max < min) {
+            return Err(self.error("invalid repetition range"));
+        }
+        Ok(Some((min, max)))
+    }
+
+    fn parse_number(&mut self) -> Result<Option<u32>, RegexError> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Ok(None);
+        }
+
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        match digits.parse::<u32>() {
+            Ok(n) if n <= MAX_REPEAT => Ok(Some(n)),
+            _ => Err(self.error("repetition count too large")),
+        }
+    }
+}
+
+struct Compiler {
+    prog: Vec<Inst>,
+}
+
+impl Compiler {
+    fn push(&mut self, inst: Inst) -> Result<usize, RegexError> {
+        if self.prog.len() >= MAX_PROGRAM {
+            return Err(RegexError {
+                pattern: None,
+                pos: 0,
+                msg: "pattern too large",
+            });
+        }
+        self.prog.push(inst);
+        Ok(self.prog.len() - 1)
+    }
+
+    //split and jmp targets aren't known until the code after them is
+    //compiled, so they get pushed as placeholders and patched afterwards
+    fn patch(&mut self, at: usize, inst: Inst) {
+        self.prog[at] = inst;
+    }
+
+    fn compile(&mut self, node: &Node) -> Result<(), RegexError> {
+        match node {
+            Node::Empty => {}
+            Node::Literal(c, ci) => {
+                let c = if *ci { casefold::fold(*c) } else { *c };
+                self.push(Inst::Char(c, *ci))?;
+            }
+            Node::Any(dot_all) => {
+                self.push(Inst::Any(*dot_all))?;
+            }
+            Node::Class(class) => {
+                self.push(Inst::Class(class.clone()))?;
+            }
+            Node::Look(look) => {
+                self.push(Inst::Assert(*look))?;
+            }
+            Node::Group(inner, index) => match index {
+                Some(i) => {
+                    self.push(Inst::Save(2 * i))?;
+                    self.compile(inner)?;
+                    self.push(Inst::Save(2 * i + 1))?;
+                }
+                None => self.compile(inner)?,
+            },
+            Node::Concat(items) => {
+                for item in items {
+                    self.compile(item)?;
+                }
+            }
+            Node::Alt(branches) => {
+                let mut jumps = Vec::new();
+                for (i, branch) in branches.iter().enumerate() {
+                    if i + 1 == branches.len() {
+                        self.compile(branch)?;
+                    } else {
+                        let split = self.push(Inst::Split(0, 0))?;
+                        self.compile(branch)?;
+                        jumps.push(self.push(Inst::Jmp(0))?);
+                        let next = self.prog.len();
+                        self.patch(split, Inst::Split(split + 1, next));
+                    }
+                }
+                let end = self.prog.len();
+                for jump in jumps {
+                    self.patch(jump, Inst::Jmp(end));
+                }
+            }
+            Node::Repeat {
+                node,
+                min,
+                max,
+                greedy,
+            } => self.compile_repeat(node, *min, *max, *greedy)?,
+        }
+        Ok(())
+    }
+
+    fn compile_repeat(&mut self, node: &Node, min: u32, max: Option<u32>, greedy: bool) -> Result<(), RegexError> {
+        for _ in 0..min {
+            self.compile(node)?;
+        }
+
+        let split_to = |body: usize, out: usize| {
+            if greedy {
+                Inst::Split(body, out)
+            } else {
+                Inst::Split(out, body)
+            }
+        };
+
+        match max {
+            None => {
+                //L: split body, out
+                //   body
+                //   jmp L
+                let split = self.push(Inst::Split(0, 0))?;
+                self.compile(node)?;
+                self.push(Inst::Jmp(split))?;
+                let out = self.prog.len();
+                self.patch(split, split_to(split + 1, out));
+            }
+            Some(max) => {
+                //each optional copy can bail out to the very end
+                let mut splits = Vec::new();
+                for _ in min..max {
+                    splits.push(self.push(Inst::Split(0, 0))?);
+                    self.compile(node)?;
+                }
+                let out = self.prog.len();
+                for split in splits {
+                    self.patch(split, split_to(split + 1, out));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn find(pattern: &str, text: &str) -> Option<(usize, usize)> {
+        Regex::build(pattern, false).unwrap().find_at(text, 0)
+    }
+
+    #[test]
+    fn literals_and_classes() {
+        assert_eq!(Some((9, 13)), find("duct", "safe, productive."));
+        assert_eq!(Some((4, 7)), find("[0-9]+", "abc 123 def"));
+        assert_eq!(Some((0, 3)), find(r"\w+", "foo bar"));
+        assert_eq!(Some((3, 4)), find("[^a-z]", "abc-def"));
+        assert_eq!(None, find("[xyz]", "abc"));
+    }
+
+    #[test]
+    fn anchors_and_boundaries() {
+        assert_eq!(Some((0, 4)), find("^Rust", "Rust: safe"));
+        assert_eq!(None, find("^safe", "Rust: safe"));
+        assert_eq!(Some((6, 10)), find("safe$", "Rust: safe"));
+        assert_eq!(Some((4, 7)), find(r"\bcat\b", "the cat sat"));
+        assert_eq!(None, find(r"\bcat\b", "concatenate"));
+        //half boundaries only care about the outside, so they work next to punctuation
+        assert_eq!(Some((1, 5)), find(r"\b{start-half}@cat\b{end-half}", " @cat."));
+        assert_eq!(None, find(r"\b{start-half}@cat", "a@cat"));
+        assert_eq!(Some((0, 5)), find(r"\b{start-half}café\b{end-half}", "café!"));
+    }
+
+    #[test]
+    fn alternation_groups_and_repetition() {
+        assert_eq!(Some((0, 3)), find("cat|dog", "cat"));
+        assert_eq!(Some((4, 7)), find("cat|dog", "hot dog"));
+        assert_eq!(Some((0, 6)), find("(ab)+", "ababab"));
+        assert_eq!(Some((0, 4)), find("a{2,3}b", "aaab"));
+        assert_eq!(Some((1, 5)), find("a{2,3}b", "aaaab"));
+        assert_eq!(Some((0, 2)), find("a.*?b", "abab"));
+        assert_eq!(Some((0, 4)), find("a.*b", "abab"));
+    }
+
+    #[test]
+    fn leftmost_first_captures() {
+        let re = Regex::build(r"(\w+)@(\w+)\.com", false).unwrap();
+        let caps = re.captures_at("mail bob@example.com now", 0).unwrap();
+        assert_eq!(3, re.captures_len());
+        assert_eq!(vec![Some(5), Some(20), Some(5), Some(8), Some(9), Some(16)], caps);
+    }
+
+    #[test]
+    fn find_all() {
+        let re = Regex::build("o+", false).unwrap();
+        assert_eq!(vec![(1, 3), (5, 6)], re.find_iter("foo bo").collect::<Vec<_>>());
+
+        let re = Regex::build("a*", false).unwrap();
+        assert_eq!(vec![(0, 1), (2, 2)], re.find_iter("ab").collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn replacing() {
+        let re = Regex::build(r"(\w+)@(\w+)\.com", false).unwrap();
+        let (replaced, spans) = re.replace_all("mail ann@rust.com or bo@x.com", "$2:${1}s $$5 $0 $9 $ $x");
+        assert_eq!("mail rust:anns $5 ann@rust.com  $ $x or x:bos $5 bo@x.com  $ $x", replaced);
+        assert_eq!(vec![(5, 36), (40, 63)], spans);
+
+        let re = Regex::build("o", false).unwrap();
+        assert_eq!("f00 b0x", re.replace_all("foo box", "0").0);
+        assert_eq!("foo", Regex::build("z", false).unwrap().replace_all("foo", "y").0);
+        assert_eq!("-a-b-", Regex::build("x*", false).unwrap().replace_all("ab", "-").0);
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let re = Regex::build("rUsT", true).unwrap();
+        assert!(re.is_match("Rust:"));
+        assert!(Regex::build("(?i)[a-c]+", false).unwrap().is_match("ABC"));
+        assert!(!Regex::build("(?i:a)b", false).unwrap().is_match("AB"));
+
+        //unicode folds, not just lowercasing
+        assert!(Regex::build("ΣΊΣΥΦΟΣ", true).unwrap().is_match("σίσυφος"));
+        assert!(Regex::build("[σ]", true).unwrap().is_match("ς"));
+        assert!(Regex::build("STRAẞE", true).unwrap().is_match("straße"));
+        assert!(!Regex::build("strasse", true).unwrap().is_match("straße"));
+        assert!(!Regex::build("i", true).unwrap().is_match("İı"));
+        assert_eq!(Some((3, 6)), Regex::build("bar", true).unwrap().find_at("İ_BAR", 0));
+    }
+
+    #[test]
+    fn no_exponential_blowup() {
+        let re = Regex::build("(a*)*b", false).unwrap();
+        assert!(!re.is_match(&"a".repeat(5000)));
+    }
+
+    #[test]
+    fn required_text() {
+        fn required(patterns: &[&str]) -> Required {
+            Regex::build_many(patterns, false).unwrap().required
+        }
+        let text = |text: &str| Required::Text(text.to_string(), false);
+
+        assert_eq!(text("hello"), required(&[r"\bhello\b"]));
+        assert_eq!(
+            Required::All(vec![text("fn "), text("("), Required::Any(vec![text("ok"), text("err")])]),
+            required(&[r"fn \w+\((ok|err)+"])
+        );
+        assert_eq!(Required::Any(vec![text("one"), text("two")]), required(&["one", "two"]));
+        //something that can match without any text rules nothing out
+        assert_eq!(Required::Anything, required(&["a|b*"]));
+        assert_eq!(Required::Anything, required(&["one", "[a-z]+"]));
+        assert_eq!(
+            Required::All(vec![text("a"), Required::Text("b".to_string(), true)]),
+            required(&["a(?i)b"])
+        );
+    }
+
+    #[test]
+    fn fuzzy_regexes() {
+        let re = Regex::fuzzy(&["receive", "colour"], 1, false);
+        assert_eq!(vec![(3, 9), (12, 17)], re.find_iter("we receve a color").collect::<Vec<_>>());
+        assert_eq!(Some((12, 17, 1)), re.find_pattern_at("we receve a color", 10));
+        assert_eq!(Some(1), re.best_fuzzy_at("we receve a color", 0).map(|m| m.distance));
+        assert_eq!(None, Regex::build("a", false).unwrap().best_fuzzy_at("a", 0));
+
+        //with one edit, one half or the other of a pattern is untouched
+        let text = |text: &str| Required::Text(text.to_string(), false);
+        let halves = vec![text("rec"), text("eive"), text("col"), text("our")];
+        assert_eq!(Required::Any(halves), re.required);
+        assert_eq!(Required::Anything, Regex::fuzzy(&["ab"], 2, false).required);
+    }
+
+    #[test]
+    fn escaped_text_is_literal() {
+        let text = r"a.b*(c)|[d]{2}^$\e+?";
+        let re = Regex::build(&escape(text), false).unwrap();
+        assert_eq!(Some((1, 1 + text.len())), re.find_at(&format!("x{text}"), 0));
+        assert!(!re.is_match("axb"));
+    }
+
+    #[test]
+    fn several_patterns() {
+        //through the vm
+        let re = Regex::build_many(&["c.t", "(d)og", "cat"], false).unwrap();
+        assert_eq!(Some((4, 7, 1)), re.find_pattern_at("hot dog", 0));
+        assert_eq!(Some((0, 3, 0)), re.find_pattern_at("cat", 0));
+        assert_eq!(Some(vec![Some(4), Some(7), Some(4), Some(5)]), re.captures_at("hot dog", 0));
+
+        //all plain text, so through the automaton, -w style assertions included
+        let re = Regex::build_many(&[r"\b{start-half}cat\b{end-half}", "dog", r"a\.b"], true).unwrap();
+        assert!(re.literals.is_some());
+        assert_eq!(None, re.literal());
+        assert_eq!(Some("a.b"), Regex::build(r"(?:a\.)b", false).unwrap().literal());
+        assert_eq!(None, Regex::build("a.b", false).unwrap().literal());
+        assert_eq!(None, Regex::build("ab", true).unwrap().literal());
+        assert_eq!(Some((13, 16, 0)), re.find_pattern_at("concatenate, CAT", 0));
+        assert_eq!(Some((1, 4, 2)), re.find_pattern_at("xA.Bdog", 0));
+        assert_eq!(vec![(1, 4), (4, 7)], re.find_iter("xa.bdog").collect::<Vec<_>>());
+        assert!(!re.is_match("axb"));
+
+        assert!(!Regex::build_many::<&str>(&[], false).unwrap().is_match("anything"));
+        assert_eq!(Some(1), Regex::build_many(&["fine", "(broken"], false).unwrap_err().pattern);
+        let huge: Vec<String> = (0..10_000).map(|i| format!("item{i}.")).collect();
+        assert_eq!(None, Regex::build_many(&huge, false).unwrap_err().pattern);
+    }
+
+    #[test]
+    fn syntax_errors() {
+        assert!(Regex::build("(abc", false).is_err());
+        assert!(Regex::build("abc)", false).is_err());
+        assert!(Regex::build("[abc", false).is_err());
+        assert!(Regex::build("*abc", false).is_err());
+        assert!(Regex::build("a{5,2}", false).is_err());
+        assert!(Regex::build(r"\q", false).is_err());
+        assert!(Regex::build(r"\b{middle}", false).is_err());
+        //an error and not a stack overflow
+        let deep = format!("{}a{}", "(".repeat(50_000), ")".repeat(50_000));
+        assert_eq!("nested too deep", Regex::build(&deep, false).unwrap_err().msg);
+        assert_eq!("nested too deep", Regex::build(&format!("a{}", "?".repeat(50_000)), false).unwrap_err().msg);
+        let both = format!("{}a{}", "(?:".repeat(200), ")*".repeat(200));
+        assert_eq!("nested too deep", Regex::build(&both, false).unwrap_err().msg);
+        let fine = format!("{}a{}", "(?:".repeat(MAX_NESTING / 2), ")?".repeat(MAX_NESTING / 2));
+        assert!(Regex::build(&fine, false).unwrap().is_match("a"));
+    }
+}
@@ -0,0 +1,710 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+use std::path::Path;
+use std::time::Instant;
+
+use crate::encoding::{self, Decoder, Encoding};
+use crate::literal::{self, Finder};
+use crate::matcher::{FindIter, Matcher};
+use crate::reader::{self, LineReader};
+use crate::records::{Field, Records};
+use crate::{Match, Stats};
+
+//the searching half of minigrep without the printing half, so other
+//programs can use it: a Searcher reads an input line by line, asks a
+//Matcher which lines are selected and tells a Sink about them. the
+//command line's output is just one Sink (see CliSink in lib.rs), a
+//program that wants the lines in a Vec or in a database writes its own
+//
+//  let mut count = 0;
+//  Searcher::new().search_str(&Literal::new("fast"), poem, &mut |_: &Match| {
+//      count += 1;
+//      Ok(true)
+//  })?;
+
+/// Receives what a Searcher finds, in the order it's in the input.
+/// Returning false from `matched` or `context` stops the search.
+pub trait Sink {
+    /// A selected line: one that matches, or with -v one that doesn't.
+    fn matched(&mut self, m: &Match) -> io::Result<bool>;
+
+    /// A selected record when the Searcher has a `field`: `m` is all of it,
+    /// and `field` the byte range in it of the field's text, which is what
+    /// matched. By default it's just another match.
+    fn matched_field(&mut self, m: &Match, _field: (usize, usize)) -> io::Result<bool> {
+        self.matched(m)
+    }
+
+    /// A selected block with `multi_line`: `m` is all the lines the matches
+    /// in it are on, and `matches` the byte ranges of those matches in it.
+    /// A match that ends with a newline ends past the end of `m.line`. By
+    /// default it's just another match.
+    fn matched_block(&mut self, m: &Match, _matches: &[(usize, usize)]) -> io::Result<bool> {
+        self.matched(m)
+    }
+
+    /// A line around a selected one, when the Searcher was asked for context.
+    fn context(&mut self, _m: &Match) -> io::Result<bool> {
+        Ok(true)
+    }
+
+    /// A selected line in a file that turned out to be binary, when the
+    /// Searcher's BinaryFiles says to tell them apart. By default it's just
+    /// another match.
+    fn binary_matched(&mut self, m: &Match) -> io::Result<bool> {
+        self.matched(m)
+    }
+
+    /// Lines were skipped between the last line and the next one, where grep prints "--".
+    fn context_break(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// The input is done, or the search was stopped.
+    fn finish(&mut self, _stats: &Stats) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+//so a closure can be a sink when all it cares about is the selected lines
+impl<F: FnMut(&Match) -> io::Result<bool>> Sink for F {
+    fn matched(&mut self, m: &Match) -> io::Result<bool> {
+        self(m)
+    }
+}
+
+/// What to do about files with binary data in them (NUL bytes, or bytes
+/// that aren't utf-8), like grep's --binary-files.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum BinaryFiles {
+    #[default]
+    Binary, //selected lines go to Sink::binary_matched once there's been binary data
+    Text, //-a, no difference from any other file
+    WithoutMatch, //stop searching the file as soon as there's binary data
+}
+
+/// How to search, independent of what to search for (the Matcher) and of
+/// what happens to the results (the Sink).
+#[derive(Debug, Clone, Default)]
+pub struct Searcher {
+    pub invert_match: bool,    //select the lines that don't match
+    pub multi_line: bool,      //-U, matches can have newlines in them, see search_whole
+    pub before_context: usize, //lines of context before every selected line
+    pub after_context: usize,  //and after it
+    pub search_zip: bool,      //decompress gzip files, see reader::open
+    pub binary: BinaryFiles,
+    pub field: Option<Field>, //match one field of records instead of lines, see search_records
+    pub encoding: Encoding,   //what input without a byte order mark is in
+}
+
+impl Searcher {
+    pub fn new() -> Searcher {
+        Searcher::default()
+    }
+
+    fn has_context(&self) -> bool {
+        self.before_context > 0 || self.after_context > 0
+    }
+
+    /// Searches a file, or stdin if `path` is "-".
+    pub fn search_path<M, S>(&self, matcher: &M, path: &Path, sink: &mut S) -> io::Result<Stats>
+    where
+        M: Matcher + ?Sized,
+        S: Sink + ?Sized,
+    {
+        let reader = reader::open(&path.to_string_lossy(), self.search_zip)?;
+        self.search_reader(matcher, reader, sink)
+    }
+
+    pub fn search_str<M, S>(&self, matcher: &M, text: &str, sink: &mut S) -> io::Result<Stats>
+    where
+        M: Matcher + ?Sized,
+        S: Sink + ?Sized,
+    {
+        self.search_reader(matcher, text.as_bytes(), sink)
+    }
+
+    /// Searches anything that can be read line by line. The stats count
+    /// everything but `matches`, finding every match on a line is up to
+    /// sinks that need them. Input that isn't utf-8, going by its byte
+    /// order mark or else by `encoding`, is decoded first.
+    pub fn search_reader<M, R, S>(&self, matcher: &M, mut reader: R, sink: &mut S) -> io::Result<Stats>
+    where
+        M: Matcher + ?Sized,
+        R: BufRead,
+        S: Sink + ?Sized,
+    {
+        let start = Instant::now();
+        let mut stats = Stats {
+            searches: 1,
+            ..Stats::default()
+        };
+        let (encoding, bom) = encoding::bom(reader.fill_buf()?).unwrap_or((self.encoding, 0));
+        reader.consume(bom);
+        match encoding {
+            Encoding::Utf8 => self.search_text(matcher, reader, sink, &mut stats)?,
+            _ => self.search_text(matcher, Decoder::new(reader, encoding), sink, &mut stats)?,
+        }
+
+        if stats.matched_lines > 0 {
+            stats.searches_with_match = 1;
+        }
+        stats.elapsed = start.elapsed();
+        sink.finish(&stats)?;
+        Ok(stats)
+    }
+
+    //utf-8 text, however it's split into what gets matched
+    fn search_text<M, R, S>(&self, matcher: &M, reader: R, sink: &mut S, stats: &mut Stats) -> io::Result<()>
+    where
+        M: Matcher + ?Sized,
+        R: BufRead,
+        S: Sink + ?Sized,
+    {
+        if let Some(field) = &self.field {
+            self.search_records(field, matcher, reader, sink, stats)
+        } else if self.multi_line {
+            self.search_whole(matcher, reader, sink, stats)
+        } else {
+            self.search_lines(matcher, reader, sink, stats)
+        }
+    }
+
+    fn search_lines<M, R, S>(&self, matcher: &M, mut reader: R, sink: &mut S, stats: &mut Stats) -> io::Result<()>
+    where
+        M: Matcher + ?Sized,
+        R: BufRead,
+        S: Sink + ?Sized,
+    {
+        //like grep, binary data is looked for at the start of the file and then
+        //in the lines that get read, but not in lines skipped by the finder
+        let check_binary = self.binary != BinaryFiles::Text;
+        let mut binary = check_binary && reader::is_binary(reader.fill_buf()?);
+        let mut lines = LineReader::new(reader);
+
+        //when the lines that don't match are never looked at (no -v, no
+        //context) and the pattern is plain text, skip straight to the lines
+        //that have it instead of going through every line
+        let finder = match matcher.literal() {
+            Some(text) if !self.invert_match && !self.has_context() => Some(Finder::new(text.as_bytes())),
+            _ => None,
+        };
+
+        let mut context = Context::new(self);
+        loop {
+            binary = binary || (check_binary && lines.saw_invalid_utf8());
+            let line = match &finder {
+                Some(finder) => lines.next_line_containing(finder)?,
+                None => lines.next_line()?,
+            };
+            let line = match line {
+                Some(line) => line,
+                None => break,
+            };
+            binary = binary || (check_binary && literal::memchr(0, line.line.as_bytes()).is_some());
+            if binary && self.binary == BinaryFiles::WithoutMatch {
+                break;
+            }
+
+            //the finder only hands back lines with the needle in them
+            let keep_going = if finder.is_some() || matcher.is_match(line.line) != self.invert_match {
+                stats.matched_lines += 1;
+                context.selected(sink, &line, line.line_number, binary, Found::Line)?
+            } else {
+                context.other(sink, &line, line.line_number)?
+            };
+            if !keep_going {
+                break;
+            }
+        }
+
+        stats.bytes_searched = lines.bytes_read();
+        Ok(())
+    }
+
+    //-U: a match can go on past the end of a line, so there are no lines to
+    //go through one at a time. the whole input is read and searched in one
+    //go, and every match becomes a block of the whole lines it touches.
+    //a block is one Match with newlines in it, selected or not as a unit,
+    //and the lines outside of blocks are one Match each like always
+    fn search_whole<M, R, S>(&self, matcher: &M, mut reader: R, sink: &mut S, stats: &mut Stats) -> io::Result<()>
+    where
+        M: Matcher + ?Sized,
+        R: BufRead,
+        S: Sink + ?Sized,
+    {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        stats.bytes_searched = bytes.len();
+        //there's no telling which part of the file is which, so any binary
+        //data at all makes all of it binary
+        let binary = self.binary != BinaryFiles::Text && reader::is_binary(&bytes);
+        if binary && self.binary == BinaryFiles::WithoutMatch {
+            return Ok(());
+        }
+        let text = String::from_utf8_lossy(&bytes);
+
+        let mut blocks = blocks(matcher, &text).into_iter().peekable();
+        let mut context = Context::new(self);
+        let mut pos = 0;
+        //where pos is in bytes, for -b. the lossy text has three bytes for
+        //every invalid one, but the same newlines, so lines line up
+        let mut byte_offset = 0;
+        let mut line_number = 1;
+        let mut matches = Vec::new(); //the ones in the block, from where it starts
+        while pos < text.len() {
+            matches.clear();
+            let (end, selected) = match blocks.peek() {
+                Some(&(start, end, _)) if start == pos => {
+                    let (_, _, in_block) = blocks.next().unwrap();
+                    matches.extend(in_block.into_iter().map(|(start, end)| (start - pos, end - pos)));
+                    (end, !self.invert_match)
+                }
+                _ => {
+                    let end = literal::memchr(b'\n', &text.as_bytes()[pos..]).map_or(text.len(), |nl| pos + nl);
+                    (end, self.invert_match)
+                }
+            };
+            let block = &text[pos..end];
+            let lines = literal::count(b'\n', block.as_bytes()) + 1;
+            let m = Match {
+                line_number,
+                byte_offset,
+                line: block.strip_suffix('\r').unwrap_or(block),
+            };
+            let last_line = line_number + lines - 1;
+
+            let keep_going = if selected {
+                stats.matched_lines += lines;
+                //with -v the selected lines are the ones outside of blocks, without matches
+                let found = if self.invert_match { Found::Line } else { Found::Block(&matches) };
+                context.selected(sink, &m, last_line, binary, found)?
+            } else {
+                context.other(sink, &m, last_line)?
+            };
+            if !keep_going {
+                break;
+            }
+            line_number = last_line + 1;
+            pos = end + 1; //past the newline
+            for _ in 0..lines {
+                byte_offset += literal::memchr(b'\n', &bytes[byte_offset..]).map_or(bytes.len() - byte_offset, |nl| nl + 1);
+            }
+        }
+        Ok(())
+    }
+
+    //--csv, --tsv and --jsonl: like search_lines, but a unit is a record (one
+    //or more lines) and whether it's selected is up to what its field says.
+    //-c counts records. a record without the field is one that doesn't match
+    fn search_records<M, R, S>(
+        &self,
+        field: &Field,
+        matcher: &M,
+        mut reader: R,
+        sink: &mut S,
+        stats: &mut Stats,
+    ) -> io::Result<()>
+    where
+        M: Matcher + ?Sized,
+        R: BufRead,
+        S: Sink + ?Sized,
+    {
+        let check_binary = self.binary != BinaryFiles::Text;
+        let mut binary = check_binary && reader::is_binary(reader.fill_buf()?);
+        let mut records = Records::new(reader, field);
+
+        let mut context = Context::new(self);
+        loop {
+            binary = binary || (check_binary && records.saw_invalid_utf8());
+            let record = match records.next_record() {
+                Ok(Some(record)) => record,
+                Ok(None) => break,
+                //no such field in the header. what's been read so far still counts
+                Err(err) => {
+                    stats.bytes_searched = records.bytes_read();
+                    return Err(err);
+                }
+            };
+            binary = binary || (check_binary && literal::memchr(0, record.text.as_bytes()).is_some());
+            if binary && self.binary == BinaryFiles::WithoutMatch {
+                break;
+            }
+            let m = Match {
+                line_number: record.line_number,
+                byte_offset: record.byte_offset,
+                line: record.text,
+            };
+
+            let matched = record.value.as_ref().filter(|value| matcher.is_match(&value.text));
+            let keep_going = if matched.is_some() != self.invert_match {
+                stats.matched_lines += 1;
+                let found = matched.map_or(Found::Line, |value| Found::Field(value.span));
+                context.selected(sink, &m, record.last_line, binary, found)?
+            } else {
+                context.other(sink, &m, record.last_line)?
+            };
+            if !keep_going {
+                break;
+            }
+        }
+
+        stats.bytes_searched = records.bytes_read();
+        Ok(())
+    }
+}
+
+type Block = (usize, usize, Vec<(usize, usize)>); //start, end and the matches in between
+
+//the whole lines every match in `text` is on, as byte ranges without the
+//final newline, and the matches in each. matches that share a line share
+//a block, like grep puts all the matches on a line on the one line
+fn blocks<M: Matcher + ?Sized>(matcher: &M, text: &str) -> Vec<Block> {
+    let mut blocks: Vec<Block> = Vec::new();
+    for (start, end) in FindIter::new(matcher, text) {
+        let first = literal::memrchr(b'\n', &text.as_bytes()[..start]).map_or(0, |nl| nl + 1);
+        if first == text.len() {
+            break; //an empty match after the last newline isn't on any line
+        }
+        //empty matches are on a line, but there's nothing in them to show
+        let matches = if end > start { vec![(start, end)] } else { Vec::new() };
+        //a match that ends with a newline ends on the line that newline ends
+        let last = if end > start { end - 1 } else { start };
+        let end = literal::memchr(b'\n', &text.as_bytes()[last..]).map_or(text.len(), |nl| last + nl);
+        match blocks.last_mut() {
+            Some(block) if first <= block.1 => {
+                block.1 = block.1.max(end);
+                block.2.extend(matches);
+            }
+            _ => blocks.push((first, end, matches)),
+        }
+    }
+    blocks
+}
+
+//what the sink gets told about a selected unit besides the Match itself
+enum Found<'a> {
+    Line,
+    Field((usize, usize)),       //the byte range of the field that matched, in a record
+    Block(&'a [(usize, usize)]), //the matches in a -U block
+}
+
+//context has to be remembered as we go: the last few lines are kept in
+//case a selected line shows up (before context), and after one the next
+//few are passed on no matter what (after context)
+struct Context {
+    before: VecDeque<(usize, usize, String)>, //(line number, byte offset, line)
+    before_context: usize,
+    after_context: usize,
+    after_left: usize,
+    last_sent: Option<usize>, //the line number of the last line the sink got
+}
+
+impl Context {
+    fn new(searcher: &Searcher) -> Context {
+        Context {
+            before: VecDeque::with_capacity(searcher.before_context),
+            before_context: searcher.before_context,
+            after_context: searcher.after_context,
+            after_left: 0,
+            last_sent: None,
+        }
+    }
+
+    //`last_line` is where `m` ends, which is further on than where it
+    //starts when it's a -U block or a record. `field` is where the field
+    //that matched is, for records
+    fn selected<S>(
+        &mut self,
+        sink: &mut S,
+        m: &Match,
+        last_line: usize,
+        binary: bool,
+        found: Found,
+    ) -> io::Result<bool>
+    where
+        S: Sink + ?Sized,
+    {
+        //no context for binary data, it wouldn't be anything readable
+        if binary {
+            self.before.clear();
+            self.after_left = 0;
+            return sink.binary_matched(m);
+        }
+
+        let first = self.before.front().map_or(m.line_number, |line| line.0);
+        let has_context = self.before_context > 0 || self.after_context > 0;
+        if has_context && self.last_sent.is_some_and(|last| last + 1 != first) {
+            sink.context_break()?;
+        }
+        for (line_number, byte_offset, text) in self.before.drain(..) {
+            let context = Match {
+                line_number,
+                byte_offset,
+                line: &text,
+            };
+            if !sink.context(&context)? {
+                return Ok(false);
+            }
+        }
+
+        self.last_sent = Some(last_line);
+        self.after_left = self.after_context;
+        match found {
+            Found::Line => sink.matched(m),
+            Found::Field(field) => sink.matched_field(m, field),
+            Found::Block(matches) => sink.matched_block(m, matches),
+        }
+    }
+
+    fn other<S: Sink + ?Sized>(&mut self, sink: &mut S, m: &Match, last_line: usize) -> io::Result<bool> {
+        if self.after_left > 0 {
+            self.after_left -= 1;
+            self.last_sent = Some(last_line);
+            return sink.context(m);
+        }
+        if self.before_context > 0 {
+            if self.before.len() == self.before_context {
+                self.before.pop_front();
+            }
+            self.before.push_back((m.line_number, m.byte_offset, m.line.to_string()));
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::matcher::Literal;
+
+    //writes down every event as text, the way grep -n would show it
+    #[derive(Default)]
+    struct Events(Vec<String>);
+
+    impl Sink for Events {
+        fn matched(&mut self, m: &Match) -> io::Result<bool> {
+            self.0.push(format!("{}:{}", m.line_number, m.line));
+            Ok(true)
+        }
+
+        //the field goes in brackets after the record
+        fn matched_field(&mut self, m: &Match, (start, end): (usize, usize)) -> io::Result<bool> {
+            self.0.push(format!("{}:{} [{}]", m.line_number, m.line, &m.line[start..end]));
+            Ok(true)
+        }
+
+        fn context(&mut self, m: &Match) -> io::Result<bool> {
+            self.0.push(format!("{}-{}", m.line_number, m.line));
+            Ok(true)
+        }
+
+        fn context_break(&mut self) -> io::Result<()> {
+            self.0.push("--".to_string());
+            Ok(())
+        }
+
+        fn finish(&mut self, stats: &Stats) -> io::Result<()> {
+            self.0.push(format!("{} lines", stats.matched_lines));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn context_events() {
+        let searcher = Searcher {
+            before_context: 1,
+            after_context: 1,
+            ..Searcher::new()
+        };
+        let mut events = Events::default();
+        let contents = "1\n2\nhit 3\n4\n5\n6\n7\nhit 8\nhit 9\n10\n";
+        searcher.search_str(&Literal::new("hit"), contents, &mut events).unwrap();
+        assert_eq!(
+            vec!["2-2", "3:hit 3", "4-4", "--", "7-7", "8:hit 8", "9:hit 9", "10-10", "3 lines"],
+            events.0
+        );
+    }
+
+    #[test]
+    fn binary_data() {
+        let contents = "text hit\nmore\x00 hit\nlast hit\n";
+        let hit = Literal::new("hit");
+        let mut binary_lines = Vec::new();
+        let mut sink = BinaryLines(&mut binary_lines);
+        //a small buffer, so the start of the file that gets checked is only the first line
+        let small = || io::BufReader::with_capacity(9, contents.as_bytes());
+        let stats = Searcher::new().search_reader(&hit, small(), &mut sink).unwrap();
+        //the first line comes before the NUL, and the sink stopped at the first binary one
+        assert_eq!((2, vec![(1, false), (2, true)]), (stats.matched_lines, binary_lines));
+
+        let searcher = Searcher {
+            binary: BinaryFiles::WithoutMatch,
+            ..Searcher::new()
+        };
+        let mut lines = Vec::new();
+        let mut sink = |m: &Match| {
+            lines.push(m.line_number);
+            Ok(true)
+        };
+        searcher.search_reader(&hit, small(), &mut sink).unwrap();
+        assert_eq!(vec![1], lines);
+
+        //a NUL right at the start makes the whole file binary, with -a it's text like any other
+        let mut binary_lines = Vec::new();
+        for binary in [BinaryFiles::Binary, BinaryFiles::Text] {
+            let searcher = Searcher { binary, ..Searcher::new() };
+            searcher.search_str(&hit, "\x00\nhit\n", &mut BinaryLines(&mut binary_lines)).unwrap();
+        }
+        assert_eq!(vec![(2, true), (2, false)], binary_lines);
+    }
+
+    #[test]
+    fn multi_line_blocks() {
+        let searcher = Searcher {
+            multi_line: true,
+            before_context: 1,
+            ..Searcher::new()
+        };
+        let contents = "1\nError: a\n  at x\n  at y\n5\n6 x\n7\nError: b\n  at z";
+        let re = crate::Regex::build(r"Error.*(\n  at .*)+|6", false).unwrap();
+        let mut events = Events::default();
+        searcher.search_str(&re, contents, &mut events).unwrap();
+        //a block counts every line in it, and its context is around the whole of it
+        assert_eq!(
+            vec!["1-1", "2:Error: a\n  at x\n  at y", "5-5", "6:6 x", "7-7", "8:Error: b\n  at z", "6 lines"],
+            events.0
+        );
+
+        //matches on the same lines are the one block, and -v is every line not in one
+        let mut lines = Vec::new();
+        let mut sink = |m: &Match| {
+            lines.push((m.line_number, m.last_line_number()));
+            Ok(true)
+        };
+        let searcher = Searcher {
+            multi_line: true,
+            ..Searcher::new()
+        };
+        searcher.search_str(&Literal::new("y\n5\n6"), contents, &mut sink).unwrap();
+        let invert = Searcher {
+            invert_match: true,
+            ..searcher
+        };
+        invert.search_str(&Literal::new("x\n  at"), contents, &mut sink).unwrap();
+        assert_eq!(vec![(4, 6), (1, 1), (2, 2), (5, 5), (6, 6), (7, 7), (8, 8), (9, 9)], lines);
+    }
+
+    #[test]
+    fn multi_line_byte_offsets() {
+        //the same offsets as line by line, counting the invalid bytes as they are in the file
+        let contents = b"a\xff\xfeok\nzz hi\nhi\n";
+        let mut offsets = Vec::new();
+        for multi_line in [false, true] {
+            let searcher = Searcher {
+                multi_line,
+                ..Searcher::new()
+            };
+            let mut sink = |m: &Match| {
+                offsets.push(m.byte_offset);
+                Ok(true)
+            };
+            searcher.search_reader(&Literal::new("hi"), &contents[..], &mut sink).unwrap();
+        }
+        assert_eq!(vec![6, 12, 6, 12], offsets);
+    }
+
+    #[test]
+    fn records() {
+        let searcher = Searcher {
+            field: Some(Field::csv("city")),
+            after_context: 1,
+            ..Searcher::new()
+        };
+        let contents = "name,city\nana,Paris\nbo,\"Lyon\nnot Paris\"\nParis,Nice\ncy,Paris\n";
+        let mut events = Events::default();
+        searcher.search_str(&Literal::new("Paris"), contents, &mut events).unwrap();
+        //the header is neither, a record is all of its lines, and the name column doesn't count
+        assert_eq!(
+            vec![
+                "2:ana,Paris [Paris]",
+                "3:bo,\"Lyon\nnot Paris\" [Lyon\nnot Paris]",
+                "5-Paris,Nice",
+                "6:cy,Paris [Paris]",
+                "3 lines"
+            ],
+            events.0
+        );
+
+        let invert = Searcher {
+            invert_match: true,
+            after_context: 0,
+            ..searcher
+        };
+        let mut events = Events::default();
+        invert.search_str(&Literal::new("Paris"), contents, &mut events).unwrap();
+        assert_eq!(vec!["5:Paris,Nice", "1 lines"], events.0);
+
+        let searcher = Searcher {
+            field: Field::jsonl(".user.name"),
+            ..Searcher::new()
+        };
+        let contents = "{\"user\":{\"name\":\"ana\"},\"by\":\"bo\"}\n{\"user\":{\"name\":\"bo\"}}\n{\"user\":null}\n";
+        let mut events = Events::default();
+        searcher.search_str(&Literal::new("bo"), contents, &mut events).unwrap();
+        assert_eq!(vec![r#"2:{"user":{"name":"bo"}} [bo]"#, "1 lines"], events.0);
+    }
+
+    #[test]
+    fn encodings() {
+        //utf-16 has a NUL in every ascii char, it's still text once it's decoded
+        let mut utf16 = vec![0xff, 0xfe];
+        utf16.extend("first\r\nsecond hit\r\nthird\r\nhit 4\r\n".encode_utf16().flat_map(u16::to_le_bytes));
+        let mut events = Events::default();
+        Searcher::new().search_reader(&Literal::new("hit"), &utf16[..], &mut events).unwrap();
+        assert_eq!(vec!["2:second hit", "4:hit 4", "2 lines"], events.0);
+
+        //the byte order mark isn't part of the first line, and without one it's up to `encoding`
+        let mut events = Events::default();
+        Searcher::new().search_str(&crate::Regex::build("^hit", false).unwrap(), "\u{feff}hit\n", &mut events).unwrap();
+        let latin1 = Searcher {
+            encoding: Encoding::Latin1,
+            ..Searcher::new()
+        };
+        latin1.search_reader(&Literal::new("café"), &b"un caf\xe9\n"[..], &mut events).unwrap();
+        assert_eq!(vec!["1:hit", "1 lines", "1:un café", "1 lines"], events.0);
+    }
+
+    //(line number, whether it was a binary match), stopping at the first binary one
+    struct BinaryLines<'a>(&'a mut Vec<(usize, bool)>);
+
+    impl Sink for BinaryLines<'_> {
+        fn matched(&mut self, m: &Match) -> io::Result<bool> {
+            self.0.push((m.line_number, false));
+            Ok(true)
+        }
+
+        fn binary_matched(&mut self, m: &Match) -> io::Result<bool> {
+            self.0.push((m.line_number, true));
+            Ok(false)
+        }
+    }
+
+    #[test]
+    fn closures_and_stopping() {
+        let mut seen = Vec::new();
+        let searcher = Searcher {
+            invert_match: true,
+            ..Searcher::new()
+        };
+        let stats = searcher
+            .search_str(&Literal::ignore_case("RUST"), "Rust:\nsafe\nfast\nrusty", &mut |m: &Match| {
+                seen.push(m.line.to_string());
+                Ok(seen.len() < 2)
+            })
+            .unwrap();
+        //the search stopped at the second selected line, so line 4 was never read
+        assert_eq!(vec!["safe", "fast"], seen);
+        assert_eq!((2, 16), (stats.matched_lines, stats.bytes_searched));
+    }
+}
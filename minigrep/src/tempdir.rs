@@ -0,0 +1,40 @@
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::{env, process};
+
+//a directory for a test to put files in. the name has the process id in it
+//so two test runs at once don't share one, and it's removed when it's
+//dropped, which also happens when an assert fails halfway through a test
+
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// An empty directory called minigrep-`name`-<pid> in the system's temp dir.
+    pub fn new(name: &str) -> TempDir {
+        let path = env::temp_dir().join(format!("minigrep-{name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&path); //whatever a test run that got killed left behind
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
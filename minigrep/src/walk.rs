@@ -0,0 +1,260 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::glob::{Glob, GlobError};
+use crate::index;
+
+//the names of the files we read ignore rules from, in every directory we visit
+const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+//one line of a .gitignore
+#[derive(Debug)]
+struct Rule {
+    glob: Glob,
+    negated: bool,  //"!pattern" un-ignores things
+    dir_only: bool, //"pattern/" only matches directories
+}
+
+//all the rules from the ignore files of one directory
+#[derive(Debug)]
+struct IgnoreFile {
+    dir: PathBuf,
+    rules: Vec<Rule>,
+}
+
+impl IgnoreFile {
+    fn parse(dir: &Path, contents: &str) -> IgnoreFile {
+        let mut rules = Vec::new();
+
+        for line in contents.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (negated, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+
+            //a pattern with a slash in it is relative to the directory of the
+            //ignore file, otherwise it can match at any depth
+            let pattern = if line.contains('/') {
+                line.trim_start_matches('/').to_string()
+            } else {
+                format!("**/{line}")
+            };
+
+            //a broken line in someone's .gitignore shouldn't stop the search
+            if let Ok(glob) = Glob::build(&pattern) {
+                rules.push(Rule {
+                    glob,
+                    negated,
+                    dir_only,
+                });
+            }
+        }
+
+        IgnoreFile {
+            dir: dir.to_path_buf(),
+            rules,
+        }
+    }
+
+    //Some(true) if ignored, Some(false) if explicitly un-ignored with !,
+    //None if no rule in this file talks about the path
+    fn check(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.dir).ok()?;
+        let relative = slash_path(relative);
+
+        //the last matching rule wins, so look at them back to front
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.glob.is_match(&relative))
+            .map(|rule| !rule.negated)
+    }
+}
+
+//turns a path into a string with / separators so globs work the same everywhere
+fn slash_path(path: &Path) -> String {
+    let parts: Vec<String> = path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    parts.join("/")
+}
+
+//a path we couldn't look inside. these get reported one at a time by the
+//caller so one unreadable directory doesn't stop the whole search
+#[derive(Debug)]
+pub struct WalkError {
+    pub path: PathBuf,
+    pub err: io::Error,
+}
+
+pub struct Walker {
+    include: Vec<Glob>,
+    exclude: Vec<Glob>,
+}
+
+impl Walker {
+    pub fn build(include: &[String], exclude: &[String]) -> Result<Walker, GlobError> {
+        //collect() can turn an iterator of Results into a Result of a Vec,
+        //stopping at the first Err
+        let include = include.iter().map(|g| Glob::build(g)).collect::<Result<Vec<_>, _>>()?;
+        let exclude = exclude.iter().map(|g| Glob::build(g)).collect::<Result<Vec<_>, _>>()?;
+        Ok(Walker { include, exclude })
+    }
+
+    /// Expands the paths given on the command line into the list of files to
+    /// search. Directories are walked recursively in sorted order, skipping
+    /// anything the ignore files or --exclude rule out. Files named directly
+    /// are always searched, just like grep does. Directories that can't be
+    /// read show up as an Err in the spot where their files would have been.
+    pub fn files(&self, paths: &[String]) -> Vec<Result<PathBuf, WalkError>> {
+        let mut files = Vec::new();
+
+        for path in paths {
+            let path = PathBuf::from(path);
+            if path.is_dir() {
+                let mut ignores = Vec::new();
+                self.walk_dir(&path, &path, &mut ignores, &mut files);
+            } else {
+                files.push(Ok(path));
+            }
+        }
+
+        files
+    }
+
+    //root is the directory the walk started in, globs with a / in them are
+    //about the paths below it
+    fn walk_dir(
+        &self,
+        root: &Path,
+        dir: &Path,
+        ignores: &mut Vec<IgnoreFile>,
+        files: &mut Vec<Result<PathBuf, WalkError>>,
+    ) {
+        let entries = match fs::read_dir(dir).and_then(|entries| entries.collect::<io::Result<Vec<_>>>()) {
+            Ok(entries) => entries,
+            Err(err) => {
+                files.push(Err(WalkError {
+                    path: dir.to_path_buf(),
+                    err,
+                }));
+                return;
+            }
+        };
+
+        //the rules of this directory only apply below it, so remember how
+        //many there were before and drop ours again on the way out
+        let outer = ignores.len();
+        for name in IGNORE_FILES {
+            if let Ok(contents) = fs::read_to_string(dir.join(name)) {
+                ignores.push(IgnoreFile::parse(dir, &contents));
+            }
+        }
+
+        let mut entries = entries;
+        entries.sort_by_key(|entry| entry.file_name()); //read_dir has no order, but our output should
+
+        for entry in entries {
+            let path = entry.path();
+            let file_type = match entry.file_type() {
+                Ok(file_type) => file_type,
+                Err(err) => {
+                    files.push(Err(WalkError { path, err }));
+                    continue;
+                }
+            };
+            let is_dir = file_type.is_dir();
+
+            //like grep -r we don't follow symlinks found while walking, which
+            //also keeps us out of symlink loops
+            if file_type.is_symlink() || entry.file_name() == ".git" {
+                continue;
+            }
+            //an index is all trigrams and would match nearly anything, and so
+            //would one a build was still writing or didn't get to finish
+            if entry.file_name() == index::FILE_NAME || entry.file_name() == index::TEMP_FILE_NAME {
+                continue;
+            }
+            if self.is_ignored(root, &path, is_dir, ignores) {
+                continue;
+            }
+
+            if is_dir {
+                self.walk_dir(root, &path, ignores, files);
+            } else if self.is_included(root, &path) {
+                files.push(Ok(path));
+            }
+        }
+
+        ignores.truncate(outer);
+    }
+
+    fn is_ignored(&self, root: &Path, path: &Path, is_dir: bool, ignores: &[IgnoreFile]) -> bool {
+        let name = slash_path(path.strip_prefix(root).unwrap_or(path));
+        if self.exclude.iter().any(|g| g.matches_path(&name)) {
+            return true;
+        }
+
+        //the ignore file closest to the path has the final say
+        ignores.iter().rev().find_map(|ignore| ignore.check(path, is_dir)).unwrap_or(false)
+    }
+
+    fn is_included(&self, root: &Path, path: &Path) -> bool {
+        let name = slash_path(path.strip_prefix(root).unwrap_or(path));
+        self.include.is_empty() || self.include.iter().any(|g| g.matches_path(&name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tempdir::TempDir;
+
+    #[test]
+    fn gitignore_rules() {
+        let dir = Path::new("repo");
+        let ignore = IgnoreFile::parse(dir, "# build output\ntarget/\n*.log\n!keep.log\n/docs/*.md\n");
+
+        assert_eq!(Some(true), ignore.check(Path::new("repo/target"), true));
+        assert_eq!(None, ignore.check(Path::new("repo/target"), false));
+        assert_eq!(Some(true), ignore.check(Path::new("repo/a/b/debug.log"), false));
+        assert_eq!(Some(false), ignore.check(Path::new("repo/keep.log"), false));
+        assert_eq!(Some(true), ignore.check(Path::new("repo/docs/intro.md"), false));
+        assert_eq!(None, ignore.check(Path::new("repo/src/docs/intro.md"), false));
+    }
+
+    #[test]
+    fn globs_below_the_root() {
+        let dir = TempDir::new("walk");
+        fs::create_dir_all(dir.join("src/target")).unwrap();
+        fs::create_dir_all(dir.join("target")).unwrap();
+        for file in ["src/lib.rs", "src/target/out.rs", "target/out.rs", "main.rs"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        let names = |include: &[&str], exclude: &[&str], root: &Path| -> Vec<String> {
+            let include: Vec<String> = include.iter().map(|s| s.to_string()).collect();
+            let exclude: Vec<String> = exclude.iter().map(|s| s.to_string()).collect();
+            let walker = Walker::build(&include, &exclude).unwrap();
+            let files = walker.files(&[root.to_string_lossy().into_owned()]);
+            files.into_iter().map(|file| slash_path(file.unwrap().strip_prefix(&dir).unwrap())).collect()
+        };
+
+        //a name on its own is any file or directory called that
+        assert_eq!(vec!["main.rs", "src/lib.rs"], names(&[], &["target"], &dir));
+        //with a / it's the path below the directory being searched, however that's written
+        assert_eq!(vec!["main.rs", "src/target/out.rs", "target/out.rs"], names(&[], &["src/*.rs"], &dir));
+        assert_eq!(vec!["src/lib.rs", "src/target/out.rs"], names(&["src/**"], &[], &dir.join(".")));
+        assert_eq!(vec!["src/target/out.rs"], names(&["target/*"], &[], &dir.join("src")));
+    }
+}